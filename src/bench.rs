@@ -0,0 +1,78 @@
+use crate::board_view::BoardView;
+use crate::canvas::{Canvas, Orientation};
+use crate::cli::BenchArgs;
+use crate::config::Config;
+use crate::game::{Direction, Game, Mode, DEFAULT_BOARD_SIZE};
+use crate::layout::Layout;
+use anyhow::Result;
+use rand::seq::SliceRandom;
+use std::time::Instant;
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Up,
+    Direction::Down,
+    Direction::Left,
+    Direction::Right,
+];
+/// How often the whole board gets drawn to measure drawing cells.
+const BOARD_DRAWS: u32 = 50;
+
+/// Measures the game logic and rendering without a display.
+pub fn run(args: &BenchArgs, mut config: Config) -> Result<()> {
+    args.display.apply(&mut config);
+
+    // Game logic
+    let rules = Mode::Classic.rules(config.base);
+    let start = Instant::now();
+    let mut rng = rand::thread_rng();
+    let mut moves = 0;
+    for seed in 0..args.games as u64 {
+        let mut game = Game::new(Mode::Classic, rules, DEFAULT_BOARD_SIZE, seed, 0);
+        while game.can_move() {
+            if game.play(*DIRECTIONS.choose(&mut rng).unwrap()) {
+                game.spawn_tile();
+                moves += 1;
+            }
+        }
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "Played {} random games with {} moves in {:?} ({:.1} µs per move)",
+        args.games,
+        moves,
+        elapsed,
+        elapsed.as_secs_f64() * 1e6 / moves.max(1) as f64
+    );
+
+    // Rendering
+    let orientation = config.orientation;
+    let mut canvas = Canvas::headless(orientation)?;
+    let layout = Layout::new(orientation, config.handedness, DEFAULT_BOARD_SIZE);
+    let mut view = BoardView::new(layout, &config, rules);
+    let start = Instant::now();
+    view.prerender(&mut canvas);
+    println!(
+        "Rendered the tiles ({:?}, {:?}, cell size {}) in {:?}",
+        config.theme,
+        config.dithering,
+        layout.board.cell_size,
+        start.elapsed()
+    );
+
+    let game = Game::new(Mode::Classic, rules, DEFAULT_BOARD_SIZE, 0, 0);
+    let start = Instant::now();
+    for _ in 0..BOARD_DRAWS {
+        view.draw_cells(&mut canvas, None, game.cells())?;
+    }
+    println!(
+        "Drew all cells {} times in {:?}{}",
+        BOARD_DRAWS,
+        start.elapsed(),
+        if orientation == Orientation::Landscape {
+            " (landscape)"
+        } else {
+            ""
+        }
+    );
+    Ok(())
+}
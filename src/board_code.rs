@@ -0,0 +1,197 @@
+use crate::game::{Cell, Game, MAX_BOARD_SIZE, MIN_BOARD_SIZE};
+use crate::rules::Rules;
+use anyhow::{Context, Result};
+
+/// Digits of the code. Each tile is stored as its rank (2 => 1,
+/// 4 => 2, .. in classic games) with 0 being an empty cell.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHIJKLMNOPQRSTUV";
+const WALL: char = 'X';
+const SEPARATOR: char = '-';
+
+/// Board position that can be shared as a short text like
+/// `4-0000100020001000-12`: board size, tiles row by row, score.
+/// Walls are written as WALL. The code doesn't include the mode,
+/// so the same one has to be used for continuing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Board {
+    pub size: usize,
+    /// Tiles hold their rank instead of their value (see Rules::rank())
+    pub ranks: Vec<Cell>,
+    pub score: u32,
+}
+
+impl Board {
+    pub fn from_game(game: &Game) -> Result<Self> {
+        let ranks = game
+            .cells()
+            .iter()
+            .map(|cell| match cell {
+                Cell::Tile(value) => game
+                    .rules
+                    .rank(*value)
+                    .map(Cell::Tile)
+                    .ok_or_else(|| anyhow!("{} can't be built with {:?}", value, game.rules)),
+                cell => Ok(*cell),
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            size: game.size,
+            ranks,
+            score: game.score,
+        })
+    }
+
+    /// Cells with the values of the tiles under the given rules.
+    pub fn cells(&self, rules: Rules) -> Result<Vec<Cell>> {
+        self.ranks
+            .iter()
+            .map(|cell| match cell {
+                Cell::Tile(rank) => rules
+                    .value(*rank)
+                    .map(Cell::Tile)
+                    .ok_or_else(|| anyhow!("Tiles of rank {} are too big for {:?}", rank, rules)),
+                cell => Ok(*cell),
+            })
+            .collect()
+    }
+
+    pub fn encode(&self) -> Result<String> {
+        let mut tiles = String::with_capacity(self.ranks.len());
+        for cell in &self.ranks {
+            let digit = match cell {
+                Cell::Empty => ALPHABET[0] as char,
+                Cell::Tile(rank) => {
+                    ensure!(
+                        *rank > 0 && (*rank as usize) < ALPHABET.len(),
+                        "Tiles of rank {} can't be encoded",
+                        rank
+                    );
+                    ALPHABET[*rank as usize] as char
+                }
+                Cell::Wall => WALL,
+            };
+            tiles.push(digit);
+        }
+        Ok(format!(
+            "{}{}{}{}{}",
+            self.size, SEPARATOR, tiles, SEPARATOR, self.score
+        ))
+    }
+
+    pub fn decode(code: &str) -> Result<Self> {
+        let parts: Vec<&str> = code.trim().split(SEPARATOR).collect();
+        ensure!(
+            parts.len() == 3,
+            "Expected board size, tiles and score separated by {:?}",
+            SEPARATOR
+        );
+        let size: usize = parts[0]
+            .parse()
+            .with_context(|| format!("Invalid board size {:?}", parts[0]))?;
+        ensure!(
+            (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&size),
+            "Boards need to be {} to {} cells wide",
+            MIN_BOARD_SIZE,
+            MAX_BOARD_SIZE
+        );
+        ensure!(
+            parts[1].len() == size * size,
+            "Expected {} tiles for a {}x{} board but got {}",
+            size * size,
+            size,
+            size,
+            parts[1].len()
+        );
+        let ranks = parts[1]
+            .chars()
+            .map(|digit| {
+                let digit = digit.to_ascii_uppercase();
+                if digit == WALL {
+                    return Ok(Cell::Wall);
+                }
+                let rank = ALPHABET
+                    .iter()
+                    .position(|c| *c as char == digit)
+                    .ok_or_else(|| anyhow!("Invalid tile {:?}", digit))?;
+                Ok(match rank {
+                    0 => Cell::Empty,
+                    rank => Cell::Tile(rank as u32),
+                })
+            })
+            .collect::<Result<Vec<Cell>>>()?;
+        let score = parts[2]
+            .parse()
+            .with_context(|| format!("Invalid score {:?}", parts[2]))?;
+        Ok(Self { size, ranks, score })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Mode;
+
+    #[test]
+    fn round_trips_with_walls() {
+        let board = Board {
+            size: 3,
+            ranks: vec![
+                Cell::Tile(1),
+                Cell::Empty,
+                Cell::Wall,
+                Cell::Empty,
+                Cell::Tile(11),
+                Cell::Empty,
+                Cell::Wall,
+                Cell::Empty,
+                Cell::Tile(31),
+            ],
+            score: 2048,
+        };
+        let code = board.encode().unwrap();
+        assert_eq!(code, "3-10X0B0X0V-2048");
+        assert_eq!(Board::decode(&code).unwrap(), board);
+    }
+
+    #[test]
+    fn round_trips_games() {
+        let board = Board::decode("4-x1200300045000x6-17").unwrap();
+        for (mode, max_tile) in [(Mode::Obstacles, 64), (Mode::Fibonacci, 13)] {
+            let rules = mode.rules(2);
+            let cells = board.cells(rules).unwrap();
+            let game = Game::with_cells(mode, rules, board.size, 0, cells, board.score);
+            assert_eq!(game.walls(), vec![(0, 0), (2, 3)]);
+            assert_eq!(game.max_tile(), max_tile);
+            assert_eq!(game.score, 17);
+            let code = Board::from_game(&game).unwrap().encode().unwrap();
+            assert_eq!(code, "4-X1200300045000X6-17");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_codes() {
+        assert!(Board::decode("4-0000-0").is_err());
+        assert!(Board::decode("2-0000-0").is_err());
+        assert!(Board::decode("3-00000000Y-0").is_err());
+        assert!(Board::decode("3-000000000").is_err());
+        assert!(Board::decode("3-000000000-x").is_err());
+        let board = Board {
+            size: 3,
+            ranks: vec![Cell::Tile(32); 9],
+            score: 0,
+        };
+        assert!(board.encode().is_err());
+    }
+
+    #[test]
+    fn rejects_tiles_the_rules_cant_build() {
+        let mut cells = vec![Cell::Empty; 9];
+        cells[0] = Cell::Tile(3);
+        let game = Game::with_cells(Mode::Classic, Rules::Powers { base: 2 }, 3, 0, cells, 0);
+        assert!(Board::from_game(&game).is_err());
+        let mut cells = vec![Cell::Empty; 9];
+        cells[0] = Cell::Tile(4);
+        let game = Game::with_cells(Mode::Fibonacci, Rules::Fibonacci, 3, 0, cells, 0);
+        assert!(Board::from_game(&game).is_err());
+    }
+}
@@ -0,0 +1,339 @@
+use crate::canvas::{color, mxcfb_rect, Canvas, Point2, Refresh, Vector2};
+use crate::config::Config;
+use crate::game::{Cell, Game};
+use crate::layout::{Layout, SCORE_FONT_SIZE, STATUS_FONT_SIZE};
+use crate::rules::Rules;
+use crate::tile_cache::TileCache;
+use anyhow::Result;
+use std::time::Instant;
+
+/// Size of the first frame of a newly spawned tile.
+const SPAWN_ANIMATION_SCALE_PERCENT: u32 = 60;
+const GRID_LINE_WIDTH: u32 = 3;
+const HEADER_BUTTON_FONT_SIZE: f32 = 45.0;
+/// Walls leave this much of their cell white around their frame
+const WALL_MARGIN_PERCENT: u32 = 8;
+const WALL_LINE_WIDTH: u32 = 4;
+/// Distance between the diagonal lines of walls
+const WALL_HATCH_SPACING_PERCENT: u32 = 12;
+
+/// How the cells get refreshed after a move.
+#[derive(Debug, Copy, Clone, PartialEq, clap::ValueEnum)]
+pub enum RefreshPolicy {
+    /// A2. Quickest, but leaves the most ghosting behind.
+    Fast,
+    /// DU
+    Normal,
+    /// GL16. Slower, but keeps the shades of gray of the tiles.
+    Quality,
+}
+
+impl RefreshPolicy {
+    fn cells(self) -> Refresh {
+        match self {
+            RefreshPolicy::Fast => Refresh::Fast,
+            RefreshPolicy::Normal => Refresh::Normal,
+            RefreshPolicy::Quality => Refresh::Gl16,
+        }
+    }
+}
+
+/// Draws a board and everything around it.
+pub struct BoardView {
+    pub layout: Layout,
+    refresh: RefreshPolicy,
+    /// See animate_spawned_tiles()
+    animations: bool,
+    tile_cache: TileCache,
+}
+
+impl BoardView {
+    pub fn new(layout: Layout, config: &Config, rules: Rules) -> Self {
+        Self {
+            layout,
+            refresh: config.refresh,
+            animations: config.animations,
+            tile_cache: TileCache::new(config.theme, config.dithering, rules),
+        }
+    }
+
+    /// Applies a changed config, board size and rules. Returns
+    /// whether the game has to be drawn again.
+    pub fn reconfigure(&mut self, config: &Config, board_size: usize, rules: Rules) -> bool {
+        self.refresh = config.refresh;
+        self.animations = config.animations;
+        // Cached tiles are rotated for the orientation they were made for
+        let restyled = !self
+            .tile_cache
+            .matches(config.theme, config.dithering, rules)
+            || self.layout.orientation != config.orientation;
+        if restyled {
+            self.tile_cache = TileCache::new(config.theme, config.dithering, rules);
+        }
+        // Also puts the board back after a versus game
+        let layout = Layout::new(config.orientation, config.handedness, board_size);
+        let moved = self.layout != layout;
+        self.layout = layout;
+        restyled || moved
+    }
+
+    /// See TileCache::prerender().
+    pub fn prerender(&mut self, canvas: &mut Canvas) {
+        self.tile_cache
+            .prerender(canvas, self.layout.board.cell_size);
+    }
+
+    /// Clears the screen and draws the whole game.
+    pub fn draw_game(&mut self, canvas: &mut Canvas, game: &Game) -> Result<()> {
+        canvas.clear();
+        canvas.update_full(Refresh::Quality);
+        self.draw_background(canvas);
+        self.draw_cells(canvas, None, game.cells())?;
+        self.draw_score(canvas, game);
+        Ok(())
+    }
+
+    pub fn draw_background(&self, canvas: &mut Canvas) {
+        // Title
+        let update_area = canvas.draw_text_centered(
+            self.layout.title_pos.x,
+            self.layout.title_pos.y,
+            "inky-2048",
+            self.layout.title_font_size,
+        );
+        canvas.update_partial(&update_area, Refresh::Gl16);
+        let update_area = canvas.draw_fixed_button(
+            self.layout.settings_button,
+            "Settings",
+            HEADER_BUTTON_FONT_SIZE,
+        );
+        canvas.update_partial(&update_area, Refresh::Normal);
+        self.draw_menu_button(canvas);
+        self.draw_grid(canvas);
+    }
+
+    pub fn draw_menu_button(&self, canvas: &mut Canvas) {
+        let update_area =
+            canvas.draw_fixed_button(self.layout.menu_button, "Menu", HEADER_BUTTON_FONT_SIZE);
+        canvas.update_partial(&update_area, Refresh::Normal);
+    }
+
+    /// The frame of the board and the lines between the cells.
+    pub fn draw_grid(&self, canvas: &mut Canvas) {
+        let board = &self.layout.board;
+        let update_area = canvas.draw_rect(
+            Point2 {
+                x: Some(board.frame_area().left as i32),
+                y: Some(board.frame_area().top as i32),
+            },
+            board.frame_area().size(),
+            GRID_LINE_WIDTH,
+        );
+
+        for i in 1..board.size as u32 {
+            let vertical_x = (update_area.left + board.cell_pitch() * i - 1) as i32;
+            canvas.draw_line(
+                Point2 {
+                    x: vertical_x,
+                    y: update_area.top as i32,
+                },
+                Point2 {
+                    x: vertical_x,
+                    y: (update_area.top + update_area.height) as i32,
+                },
+                GRID_LINE_WIDTH,
+            );
+            let horizontal_y = (update_area.top + board.cell_pitch() * i - 1) as i32;
+            canvas.draw_line(
+                Point2 {
+                    x: update_area.left as i32,
+                    y: horizontal_y,
+                },
+                Point2 {
+                    x: (update_area.left + update_area.width) as i32,
+                    y: horizontal_y,
+                },
+                GRID_LINE_WIDTH,
+            );
+        }
+
+        canvas.update_partial(&update_area, Refresh::Normal);
+        debug!("Background drawn.");
+    }
+
+    /// Leaves the score out in modes without one.
+    pub fn draw_score(&self, canvas: &mut Canvas, game: &Game) {
+        if !game.mode.shows_score() {
+            return;
+        }
+        let area = canvas.fill_rect(
+            Point2 {
+                x: Some(self.layout.score_area.left as i32),
+                y: Some(self.layout.score_area.top as i32),
+            },
+            self.layout.score_area.size(),
+            color::WHITE,
+        );
+        canvas.draw_text_centered(
+            self.layout.score_pos.x,
+            self.layout.score_pos.y,
+            &format!("Score: {}", game.score),
+            SCORE_FONT_SIZE,
+        );
+        canvas.update_partial(&area, Refresh::Normal);
+    }
+
+    /// Shows the remaining time of a time attack game or the goal
+    /// of a puzzle. A small black and white refresh, so it can
+    /// happen every second.
+    pub fn draw_status(&self, canvas: &mut Canvas, text: &str) {
+        let status_area = self.layout.status_area;
+        let area = canvas.fill_rect(
+            Point2 {
+                x: Some(status_area.left as i32),
+                y: Some(status_area.top as i32),
+            },
+            status_area.size(),
+            color::WHITE,
+        );
+        let (text_area, _) = canvas.measure_text(text, STATUS_FONT_SIZE);
+        let scale = (status_area.width as f32 / text_area.width.max(1) as f32).min(1.0);
+        canvas.draw_text_centered(
+            self.layout.status_pos.x,
+            self.layout.status_pos.y,
+            text,
+            STATUS_FONT_SIZE * scale,
+        );
+        canvas.update_partial(&area, Refresh::Normal);
+    }
+
+    /// Draws all cells that differ from `last` (or all cells if
+    /// there is no last state). Returns the markers of the refreshes.
+    pub fn draw_cells(
+        &mut self,
+        canvas: &mut Canvas,
+        last: Option<&[Cell]>,
+        current: &[Cell],
+    ) -> Result<Vec<u32>> {
+        debug!("Board: {:?}", current);
+        let start = Instant::now();
+        let mut changed_areas = Vec::new();
+        for (i, cell) in current.iter().enumerate() {
+            if let Some(last) = last {
+                if last[i] == *cell {
+                    continue;
+                }
+            }
+            changed_areas.push(self.draw_cell(canvas, i, *cell)?);
+        }
+        let markers = canvas.update_partial_batch(&changed_areas, self.refresh.cells());
+        debug!(
+            "Update of {} cells with {} refreshes took {:?}",
+            changed_areas.len(),
+            markers.len(),
+            start.elapsed()
+        );
+        Ok(markers)
+    }
+
+    /// Lets new tiles grow into their cells in two steps. Just
+    /// draws them if animations are turned off.
+    pub fn animate_spawned_tiles(
+        &mut self,
+        canvas: &mut Canvas,
+        last: &[Cell],
+        current: &[Cell],
+    ) -> Result<Vec<u32>> {
+        if !self.animations {
+            return self.draw_cells(canvas, Some(last), current);
+        }
+        let mut markers = Vec::new();
+        for (i, cell) in current.iter().enumerate() {
+            let value = match (last[i], cell) {
+                (Cell::Empty, Cell::Tile(value)) => value,
+                _ => continue,
+            };
+            let cell_area = self.layout.board.cell_area(i);
+            let size = cell_area.width * SPAWN_ANIMATION_SCALE_PERCENT / 100;
+            let tile = self.tile_cache.get(canvas, *value, size);
+            let update_area = canvas.draw_native_image(
+                Point2 {
+                    x: cell_area.left + (cell_area.width - size) / 2,
+                    y: cell_area.top + (cell_area.height - size) / 2,
+                },
+                tile,
+            );
+            markers.push(canvas.update_partial(&update_area, Refresh::Fast));
+        }
+        // The next frame would otherwise be drawn while the
+        // previous one is still being shown
+        canvas.wait_for_updates(&markers);
+        self.draw_cells(canvas, Some(last), current)
+    }
+
+    fn draw_cell(&mut self, canvas: &mut Canvas, index: usize, cell: Cell) -> Result<mxcfb_rect> {
+        let board = &self.layout.board;
+        ensure!(
+            index < board.size * board.size,
+            "Cell {} is outside of the {}x{} board",
+            index,
+            board.size,
+            board.size
+        );
+        let cell_area = board.cell_area(index);
+        let number = match cell {
+            Cell::Empty => 0,
+            Cell::Tile(number) => number,
+            Cell::Wall => {
+                draw_wall(canvas, cell_area);
+                debug!("Cell {} => wall", index);
+                return Ok(cell_area);
+            }
+        };
+        let tile = self.tile_cache.get(canvas, number, cell_area.width);
+        canvas.draw_native_image(cell_area.top_left(), tile);
+        debug!("Cell {} => {}", index, number);
+        Ok(cell_area)
+    }
+}
+
+/// A frame filled with diagonal lines. Unlike tiles, walls
+/// are drawn right onto the canvas.
+fn draw_wall(canvas: &mut Canvas, cell_area: mxcfb_rect) {
+    canvas.fill_rect(
+        Point2 {
+            x: Some(cell_area.left as i32),
+            y: Some(cell_area.top as i32),
+        },
+        cell_area.size(),
+        color::WHITE,
+    );
+    let margin = cell_area.width * WALL_MARGIN_PERCENT / 100;
+    let (left, top) = (cell_area.left + margin, cell_area.top + margin);
+    let size = cell_area.width - 2 * margin;
+    canvas.draw_rect(
+        Point2 {
+            x: Some(left as i32),
+            y: Some(top as i32),
+        },
+        Vector2 { x: size, y: size },
+        WALL_LINE_WIDTH,
+    );
+
+    // Lines from the bottom left to the top right. Each one
+    // connects the points with x + y = offset.
+    let spacing = (size * WALL_HATCH_SPACING_PERCENT / 100).max(WALL_LINE_WIDTH * 2);
+    // Keeps the lines inside the frame despite their width
+    let size = size - WALL_LINE_WIDTH;
+    for offset in (spacing..2 * size).step_by(spacing as usize) {
+        let start = Point2 {
+            x: (left + offset.saturating_sub(size)) as i32,
+            y: (top + offset.min(size)) as i32,
+        };
+        let end = Point2 {
+            x: (left + offset.min(size)) as i32,
+            y: (top + offset.saturating_sub(size)) as i32,
+        };
+        canvas.draw_line(start, end, WALL_LINE_WIDTH);
+    }
+}
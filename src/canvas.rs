@@ -1,3 +1,4 @@
+use anyhow::{Context, Result};
 use libremarkable::cgmath::vec2;
 pub use libremarkable::framebuffer::{
     cgmath::Point2, cgmath::Vector2, common::color, common::mxcfb_rect, common::DISPLAYHEIGHT,
@@ -6,18 +7,197 @@ pub use libremarkable::framebuffer::{
 };
 use libremarkable::framebuffer::{
     common::display_temp, common::dither_mode, common::waveform_mode, refresh::PartialRefreshMode,
+    screeninfo::Bitfield, screeninfo::FixScreeninfo, screeninfo::VarScreeninfo,
 };
-use libremarkable::image;
+use libremarkable::image::{self, imageops};
+use memmap2::MmapOptions;
+use rusttype::{point, FontCollection, Scale};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
 use std::ops::DerefMut;
+use std::sync::atomic::AtomicU32;
+
+/// The font libremarkable draws with. Needed to draw without a device.
+const FONT: &[u8] = include_bytes!("../res/Roboto-Regular.ttf");
+
+/// Image in the native pixel format of the framebuffer (rgb565_le).
+/// Drawing it is basically a plain memory copy.
+pub struct NativeImage {
+    pub width: u32,
+    pub height: u32,
+    data: Vec<u8>,
+}
+
+impl NativeImage {
+    /// See Canvas::native_image() for images in screen coordinates.
+    fn from_rgb(img: &image::RgbImage) -> Self {
+        let mut data = Vec::with_capacity(img.width() as usize * img.height() as usize * 2);
+        for pixel in img.pixels() {
+            data.extend_from_slice(&color::RGB(pixel[0], pixel[1], pixel[2]).as_native());
+        }
+        Self {
+            width: img.width(),
+            height: img.height(),
+            data,
+        }
+    }
+}
+
+/// How the screen is held. Everything drawn on a Canvas uses
+/// coordinates as seen by the player, which get rotated as
+/// needed before they reach the framebuffer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    Portrait,
+    /// Rotated counterclockwise, so the buttons of the device are on the right.
+    Landscape,
+}
+
+impl Orientation {
+    /// Size of the screen in this orientation.
+    pub fn size(self) -> Vector2<u32> {
+        match self {
+            Orientation::Portrait => Vector2 {
+                x: DISPLAYWIDTH as u32,
+                y: DISPLAYHEIGHT as u32,
+            },
+            Orientation::Landscape => Vector2 {
+                x: DISPLAYHEIGHT as u32,
+                y: DISPLAYWIDTH as u32,
+            },
+        }
+    }
+
+    /// Converts a position reported by the touchscreen.
+    pub fn to_screen(self, pos: Point2<u16>) -> Point2<u16> {
+        match self {
+            Orientation::Portrait => pos,
+            Orientation::Landscape => Point2 {
+                x: pos.y,
+                y: (DISPLAYWIDTH - 1).saturating_sub(pos.x),
+            },
+        }
+    }
+
+    fn point_to_framebuffer(self, pos: Point2<i32>) -> Point2<i32> {
+        match self {
+            Orientation::Portrait => pos,
+            Orientation::Landscape => Point2 {
+                x: DISPLAYWIDTH as i32 - 1 - pos.y,
+                y: pos.x,
+            },
+        }
+    }
+
+    fn rect_to_framebuffer(self, rect: &mxcfb_rect) -> mxcfb_rect {
+        match self {
+            Orientation::Portrait => *rect,
+            Orientation::Landscape => mxcfb_rect {
+                left: (DISPLAYWIDTH as u32).saturating_sub(rect.top + rect.height),
+                top: rect.left,
+                width: rect.height,
+                height: rect.width,
+            },
+        }
+    }
+}
+
+/// How a region gets refreshed. Faster refreshes come
+/// with more ghosting and less shades of gray.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Refresh {
+    /// A2. Black and white only, but very fast. Meant for animations.
+    Fast,
+    /// DU. Black and white only. Meant for regular changes.
+    Normal,
+    /// GC16. All shades of gray and no ghosting, but flashes.
+    Quality,
+    /// GL16. Shades of gray without flashing when coming from white.
+    Gl16,
+}
+
+impl Refresh {
+    fn waveform_mode(self) -> waveform_mode {
+        match self {
+            // According to libremarkable, this is basically A2
+            Refresh::Fast => waveform_mode::WAVEFORM_MODE_GLR16,
+            Refresh::Normal => waveform_mode::WAVEFORM_MODE_DU,
+            Refresh::Quality => waveform_mode::WAVEFORM_MODE_GC16,
+            Refresh::Gl16 => waveform_mode::WAVEFORM_MODE_GL16_FAST,
+        }
+    }
+
+    fn dither_mode(self) -> dither_mode {
+        match self {
+            Refresh::Fast | Refresh::Normal => dither_mode::EPDC_FLAG_USE_REMARKABLE_DITHER,
+            Refresh::Quality | Refresh::Gl16 => dither_mode::EPDC_FLAG_USE_DITHERING_PASSTHROUGH,
+        }
+    }
+}
+
+/// Merged refresh regions may contain at most this fraction
+/// of pixels that didn't need to be refreshed.
+pub const REFRESH_MERGE_MAX_WASTE: f32 = 0.35;
+/// Refreshes that are older than this are assumed to be done
+/// and won't be tracked anymore.
+const MAX_PENDING_UPDATES: usize = 16;
 
 pub struct Canvas<'a> {
     framebuffer: Box<Framebuffer<'a>>,
+    orientation: Orientation,
+    /// Drawing into memory only. Nothing gets refreshed.
+    headless: bool,
+    /// Issued partial refreshes which may still be in progress.
+    pending_updates: Vec<(mxcfb_rect, u32 /* Marker */)>,
 }
 
 impl<'a> Canvas<'a> {
-    pub fn new() -> Self {
+    pub fn new(orientation: Orientation) -> Self {
         Self {
             framebuffer: Box::new(Framebuffer::from_path("/dev/fb0")),
+            orientation,
+            headless: false,
+            pending_updates: Vec::new(),
+        }
+    }
+
+    /// Canvas that isn't backed by the display. Meant for rendering
+    /// without a device (see save_png()).
+    pub fn headless(orientation: Orientation) -> Result<Self> {
+        Ok(Self {
+            framebuffer: Box::new(memory_framebuffer()?),
+            orientation,
+            headless: true,
+            pending_updates: Vec::new(),
+        })
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    /// Applies to everything drawn from now on. Whatever is on
+    /// the screen should be drawn again.
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.orientation = orientation;
+    }
+
+    pub fn width(&self) -> u32 {
+        self.orientation.size().x
+    }
+
+    pub fn height(&self) -> u32 {
+        self.orientation.size().y
+    }
+
+    /// Everything that can be drawn on.
+    pub fn area(&self) -> mxcfb_rect {
+        mxcfb_rect {
+            left: 0,
+            top: 0,
+            width: self.width(),
+            height: self.height(),
         }
     }
 
@@ -33,36 +213,92 @@ impl<'a> Canvas<'a> {
         self.framebuffer_mut().clear();
     }
 
-    pub fn update_full(&mut self) -> u32 {
+    pub fn update_full(&mut self, refresh: Refresh) -> u32 {
+        if self.headless {
+            return 0;
+        }
         self.framebuffer_mut().full_refresh(
-            waveform_mode::WAVEFORM_MODE_GC16,
+            refresh.waveform_mode(),
             display_temp::TEMP_USE_REMARKABLE_DRAW,
-            dither_mode::EPDC_FLAG_USE_DITHERING_PASSTHROUGH,
+            refresh.dither_mode(),
             0,
             true,
         )
     }
 
-    pub fn update_partial(&mut self, region: &mxcfb_rect) -> u32 {
-        self.framebuffer_mut().partial_refresh(
+    pub fn update_partial(&mut self, region: &mxcfb_rect, refresh: Refresh) -> u32 {
+        if self.headless {
+            return 0;
+        }
+        let region = &self.orientation.rect_to_framebuffer(region);
+        // Refreshing a region that is still being refreshed
+        // would make both updates collide.
+        let mut i = 0;
+        while i < self.pending_updates.len() {
+            let (pending_region, marker) = self.pending_updates[i];
+            if rects_intersect(&pending_region, region) {
+                self.framebuffer_mut().wait_refresh_complete(marker);
+                self.pending_updates.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+
+        let marker = self.framebuffer_mut().partial_refresh(
             region,
             PartialRefreshMode::Async,
-            waveform_mode::WAVEFORM_MODE_DU,
+            refresh.waveform_mode(),
             display_temp::TEMP_USE_REMARKABLE_DRAW,
-            dither_mode::EPDC_FLAG_USE_REMARKABLE_DITHER,
+            refresh.dither_mode(),
             0, // See documentation on DRAWING_QUANT_BITS in libremarkable/framebuffer/common.rs
             false,
-        )
+        );
+        if self.pending_updates.len() >= MAX_PENDING_UPDATES {
+            self.pending_updates.remove(0);
+        }
+        self.pending_updates.push((*region, marker));
+        marker
+    }
+
+    /// Refreshes all regions using as few refreshes as possible.
+    /// See merge_regions().
+    pub fn update_partial_batch(&mut self, regions: &[mxcfb_rect], refresh: Refresh) -> Vec<u32> {
+        merge_regions(regions, REFRESH_MERGE_MAX_WASTE)
+            .iter()
+            .map(|region| self.update_partial(region, refresh))
+            .collect()
     }
 
     pub fn wait_for_update(&mut self, update_marker: u32) {
+        if self.headless {
+            return;
+        }
         self.framebuffer_mut().wait_refresh_complete(update_marker);
+        self.pending_updates
+            .retain(|(_, marker)| *marker != update_marker);
+    }
+
+    /// Waits for every refresh that may still be in progress.
+    pub fn wait_for_all_updates(&mut self) {
+        let markers: Vec<u32> = self
+            .pending_updates
+            .iter()
+            .map(|(_, marker)| *marker)
+            .collect();
+        self.wait_for_updates(&markers);
+    }
+
+    pub fn wait_for_updates(&mut self, update_markers: &[u32]) {
+        for marker in update_markers {
+            self.wait_for_update(*marker);
+        }
     }
 
     //Long text with draw_text layers on top of each other ending up in garbled output
     //This is a quick hack which tries to prevent word clipping
     //I've found that the remarkable can do about a 95 characters at 35.0 font size
     //if you're looking for a nice default that fits the whole screen
+    #[allow(clippy::too_many_arguments)]
     pub fn draw_multi_line_text(
         &mut self,
         x_pos: Option<i32>,
@@ -102,9 +338,9 @@ impl<'a> Canvas<'a> {
                         .find(|char| **char < chars_taken_so_far + max_chars_per_line)
                     {
                         None => max_chars_per_line,
-                        Some(chars) => chars - (chars_taken_so_far - 1),
+                        Some(chars) => chars + 1 - chars_taken_so_far,
                     };
-                    chars_taken_so_far = chars_taken_so_far + chars_to_take;
+                    chars_taken_so_far += chars_to_take;
                 }
                 let chunk: String = peekable.by_ref().take(chars_to_take).collect();
                 let text_rect = self.draw_text(
@@ -138,58 +374,134 @@ impl<'a> Canvas<'a> {
         }
     }
 
+    /// Dryrun of draw_text. The returned rect is relative to the
+    /// origin (baseline start) of the text. Since the glyphs are
+    /// above the baseline, `top` is usually negative and therefore
+    /// returned separately as the offset from the baseline.
+    pub fn measure_text(&mut self, text: &str, size: f32) -> (mxcfb_rect, i32) {
+        let rect = self.framebuffer_mut().draw_text(
+            Point2 {
+                x: 0.0,
+                y: DISPLAYHEIGHT as f32,
+            },
+            text,
+            size,
+            color::BLACK,
+            true,
+        );
+        let baseline_offset = rect.top as i32 - DISPLAYHEIGHT as i32;
+        (rect, baseline_offset)
+    }
+
     pub fn draw_text(&mut self, pos: Point2<Option<i32>>, text: &str, size: f32) -> mxcfb_rect {
         let mut pos = pos;
         if pos.x.is_none() || pos.y.is_none() {
             // Do dryrun to get text size
-            let rect = self.framebuffer_mut().draw_text(
-                Point2 {
-                    x: 0.0,
-                    y: DISPLAYHEIGHT as f32,
-                },
-                text,
-                size,
-                color::BLACK,
-                true,
-            );
+            let (rect, _) = self.measure_text(text, size);
 
             if pos.x.is_none() {
                 // Center horizontally
-                pos.x = Some(DISPLAYWIDTH as i32 / 2 - rect.width as i32 / 2);
+                pos.x = Some(self.width() as i32 / 2 - rect.width as i32 / 2);
             }
 
             if pos.y.is_none() {
                 // Center vertically
-                pos.y = Some(DISPLAYHEIGHT as i32 / 2 - rect.height as i32 / 2);
+                pos.y = Some(self.height() as i32 / 2 - rect.height as i32 / 2);
             }
         }
         let pos = Point2 {
-            x: pos.x.unwrap() as f32,
-            y: pos.y.unwrap() as f32,
+            x: pos.x.unwrap(),
+            y: pos.y.unwrap(),
         };
 
-        self.framebuffer_mut()
-            .draw_text(pos, text, size, color::BLACK, false)
+        if self.orientation == Orientation::Portrait {
+            return self.framebuffer_mut().draw_text(
+                pos.cast().unwrap(),
+                text,
+                size,
+                color::BLACK,
+                false,
+            );
+        }
+
+        // The framebuffer can't draw rotated text. So the covered
+        // region gets rendered in screen coordinates and copied back.
+        let (rect, baseline_offset) = self.measure_text(text, size);
+        let left = (pos.x + rect.left as i32).max(0);
+        let top = (pos.y + baseline_offset).max(0);
+        let region = mxcfb_rect {
+            left: left as u32,
+            top: top as u32,
+            width: rect.width.min(self.width().saturating_sub(left as u32)),
+            height: rect.height.min(self.height().saturating_sub(top as u32)),
+        };
+        match self.dump_rgb(region) {
+            Ok(mut img) => {
+                self.render_text(
+                    &mut img,
+                    Point2 {
+                        x: pos.x - left,
+                        y: pos.y - top,
+                    },
+                    text,
+                    size,
+                );
+                let img = self.native_image(&img);
+                self.draw_native_image(region.top_left(), &img);
+            }
+            Err(e) => error!("Failed to draw text {:?}: {:?}", text, e),
+        }
+        region
     }
 
-    fn draw_box(
+    /// Draws the text horizontally centered around `center_x`.
+    pub fn draw_text_centered(
         &mut self,
-        pos: Point2<i32>,
-        size: Vector2<u32>,
-        border_px: u32,
-        c: color,
+        center_x: i32,
+        baseline: i32,
+        text: &str,
+        size: f32,
     ) -> mxcfb_rect {
+        let (rect, _) = self.measure_text(text, size);
+        self.draw_text(
+            Point2 {
+                x: Some(center_x - rect.width as i32 / 2),
+                y: Some(baseline),
+            },
+            text,
+            size,
+        )
+    }
+
+    /// Line between two points. Anything but horizontal or vertical
+    /// lines end up with a slightly different width.
+    pub fn draw_line(&mut self, start: Point2<i32>, end: Point2<i32>, width: u32) -> mxcfb_rect {
+        let orientation = self.orientation;
+        self.framebuffer_mut().draw_line(
+            orientation.point_to_framebuffer(start),
+            orientation.point_to_framebuffer(end),
+            width,
+            color::BLACK,
+        );
+        let (left, top) = (start.x.min(end.x), start.y.min(end.y));
+        mxcfb_rect {
+            left: left.max(0) as u32,
+            top: top.max(0) as u32,
+            width: (start.x - end.x).unsigned_abs() + width,
+            height: (start.y - end.y).unsigned_abs() + width,
+        }
+    }
+
+    fn draw_box(&mut self, pos: Point2<i32>, size: Vector2<u32>, border_px: u32) -> mxcfb_rect {
         let top_left = pos;
         let top_right = pos + vec2(size.x as i32, 0);
         let bottom_left = pos + vec2(0, size.y as i32);
         let bottom_right = bottom_left + vec2(size.x as i32, 0);
 
         // top horizontal
-        self.framebuffer_mut()
-            .draw_line(top_left, top_right, border_px, c);
+        self.draw_line(top_left, top_right, border_px);
 
-        self.framebuffer_mut()
-            .draw_line(bottom_left, bottom_right, border_px, c);
+        self.draw_line(bottom_left, bottom_right, border_px);
         mxcfb_rect {
             top: pos.y as u32,
             left: pos.x as u32,
@@ -208,12 +520,12 @@ impl<'a> Canvas<'a> {
         if pos.x.is_none() || pos.y.is_none() {
             if pos.x.is_none() {
                 // Center horizontally
-                pos.x = Some(DISPLAYWIDTH as i32 / 2 - size.x as i32 / 2);
+                pos.x = Some(self.width() as i32 / 2 - size.x as i32 / 2);
             }
 
             if pos.y.is_none() {
                 // Center vertically
-                pos.y = Some(DISPLAYHEIGHT as i32 / 2 - size.y as i32 / 2);
+                pos.y = Some(self.height() as i32 / 2 - size.y as i32 / 2);
             }
         }
         let pos = Point2 {
@@ -221,14 +533,20 @@ impl<'a> Canvas<'a> {
             y: pos.y.unwrap(),
         };
 
-        self.framebuffer_mut()
-            .draw_rect(pos, size, border_px, color::BLACK);
-        mxcfb_rect {
+        let rect = mxcfb_rect {
             top: pos.y as u32,
             left: pos.x as u32,
             width: size.x,
             height: size.y,
-        }
+        };
+        let fb_rect = self.orientation.rect_to_framebuffer(&rect);
+        self.framebuffer_mut().draw_rect(
+            fb_rect.top_left().cast().unwrap(),
+            fb_rect.size(),
+            border_px,
+            color::BLACK,
+        );
+        rect
     }
 
     pub fn fill_rect(
@@ -241,12 +559,12 @@ impl<'a> Canvas<'a> {
         if pos.x.is_none() || pos.y.is_none() {
             if pos.x.is_none() {
                 // Center horizontally
-                pos.x = Some(DISPLAYWIDTH as i32 / 2 - size.x as i32 / 2);
+                pos.x = Some(self.width() as i32 / 2 - size.x as i32 / 2);
             }
 
             if pos.y.is_none() {
                 // Center vertically
-                pos.y = Some(DISPLAYHEIGHT as i32 / 2 - size.y as i32 / 2);
+                pos.y = Some(self.height() as i32 / 2 - size.y as i32 / 2);
             }
         }
         let pos = Point2 {
@@ -254,13 +572,16 @@ impl<'a> Canvas<'a> {
             y: pos.y.unwrap(),
         };
 
-        self.framebuffer_mut().fill_rect(pos, size, clr);
-        mxcfb_rect {
+        let rect = mxcfb_rect {
             top: pos.y as u32,
             left: pos.x as u32,
             width: size.x,
             height: size.y,
-        }
+        };
+        let fb_rect = self.orientation.rect_to_framebuffer(&rect);
+        self.framebuffer_mut()
+            .fill_rect(fb_rect.top_left().cast().unwrap(), fb_rect.size(), clr);
+        rect
     }
 
     pub fn draw_button(
@@ -285,6 +606,29 @@ impl<'a> Canvas<'a> {
         )
     }
 
+    /// Button with a fixed size and the text centered inside.
+    /// Useful for grids of buttons that need to line up.
+    pub fn draw_fixed_button(
+        &mut self,
+        area: mxcfb_rect,
+        text: &str,
+        font_size: f32,
+    ) -> mxcfb_rect {
+        let (text_rect, baseline_offset) = self.measure_text(text, font_size);
+        let hgap = area.width.saturating_sub(text_rect.width) / 2;
+        let vgap = area.height.saturating_sub(text_rect.height) / 2;
+        self.draw_button(
+            Point2 {
+                x: Some((area.left + hgap) as i32),
+                y: Some((area.top + vgap) as i32 - baseline_offset),
+            },
+            text,
+            font_size,
+            vgap,
+            hgap,
+        )
+    }
+
     //Text size seems to vary
     //This ignores text size so that boxes line up deterministically
     //Text ends up a bit off center though unfortunately
@@ -298,11 +642,10 @@ impl<'a> Canvas<'a> {
         let button_hitbox = self.draw_box(
             Point2 { x: 0, y: y_pos },
             Vector2 {
-                x: DISPLAYWIDTH as u32,
+                x: self.width(),
                 y: y_height,
             },
             5,
-            color::BLACK,
         );
         self.draw_text(
             Point2 {
@@ -315,75 +658,88 @@ impl<'a> Canvas<'a> {
         button_hitbox
     }
 
-    /// Image that can be overlayed white respecting the previous pixels.
-    /// This way transparent images can work.
-    fn calc_overlay_image(
-        &mut self,
-        pos: Point2<i32>,
-        img: &image::DynamicImage,
-    ) -> image::RgbImage {
-        let rgba = img.to_rgba();
-        let mut rgb = img.to_rgb();
-
-        let orig_rgb888 = rgbimage_from_u8_slice(
-            rgba.width(),
-            rgba.height(),
-            &self
-                .framebuffer_mut()
-                .dump_region(mxcfb_rect {
-                    top: pos.y as u32,
-                    left: pos.x as u32,
-                    width: rgba.width(),
-                    height: rgba.height(),
-                })
-                .unwrap(),
-        )
-        .unwrap();
-
-        for (x, y, pixel) in rgba.enumerate_pixels() {
-            let color_pix = [
-                pixel[0] as f32 / 255.0,
-                pixel[1] as f32 / 255.0,
-                pixel[2] as f32 / 255.0,
-            ];
-            let color_alpha = (255 - pixel[3]) as f32 / 255.0;
-            let orig_pixel = orig_rgb888.get_pixel(x, y);
-            let new_rgb_f32 = image::Rgb([
-                color_pix[0] * (1.0 - color_alpha) + (orig_pixel[0] as f32 / 255.0) * color_alpha,
-                color_pix[1] * (1.0 - color_alpha) + (orig_pixel[1] as f32 / 255.0) * color_alpha,
-                color_pix[2] * (1.0 - color_alpha) + (orig_pixel[2] as f32 / 255.0) * color_alpha,
-            ]);
-
-            let new_rgb_u8: image::Rgb<u8> = image::Rgb([
-                (new_rgb_f32[0] * 255.0) as u8,
-                (new_rgb_f32[1] * 255.0) as u8,
-                (new_rgb_f32[2] * 255.0) as u8,
-            ]);
-
-            rgb.put_pixel(x, y, new_rgb_u8);
+    /// Converts the image for drawing it with draw_native_image().
+    pub fn native_image(&self, img: &image::RgbImage) -> NativeImage {
+        match self.orientation {
+            Orientation::Portrait => NativeImage::from_rgb(img),
+            Orientation::Landscape => {
+                let mut native = NativeImage::from_rgb(&imageops::rotate90(img));
+                native.width = img.width();
+                native.height = img.height();
+                native
+            }
         }
+    }
+
+    pub fn draw_native_image(&mut self, pos: Point2<u32>, img: &NativeImage) -> mxcfb_rect {
+        let rect = mxcfb_rect {
+            top: pos.y,
+            left: pos.x,
+            width: img.width,
+            height: img.height,
+        };
+        let fb_rect = self.orientation.rect_to_framebuffer(&rect);
+        if let Err(e) = self.framebuffer_mut().restore_region(fb_rect, &img.data) {
+            error!("Failed to draw native image: {}", e);
+        }
+        rect
+    }
+
+    /// Reads back what is currently on the screen in `region`.
+    pub fn dump_rgb(&mut self, region: mxcfb_rect) -> Result<image::RgbImage> {
+        ensure!(
+            region.left + region.width <= self.width()
+                && region.top + region.height <= self.height(),
+            "Region {:?} is outside of the screen",
+            region
+        );
+        let fb_region = self.orientation.rect_to_framebuffer(&region);
+        let data = self
+            .framebuffer_mut()
+            .dump_region(fb_region)
+            .map_err(|e| anyhow!("Failed to dump region: {}", e))?;
+        let img = rgbimage_from_u8_slice(fb_region.width, fb_region.height, &data)
+            .ok_or_else(|| anyhow!("Failed to convert dumped region"))?;
+        Ok(match self.orientation {
+            Orientation::Portrait => img,
+            Orientation::Landscape => imageops::rotate270(&img),
+        })
+    }
 
-        rgb
+    /// Saves everything drawn so far.
+    pub fn save_png(&mut self, path: &std::path::Path) -> Result<()> {
+        self.dump_rgb(self.area())?
+            .save(path)
+            .with_context(|| format!("Failed to save {:?}", path))
     }
 
-    pub fn draw_image(
+    /// Like draw_text but renders black text into `img` instead of the
+    /// framebuffer. `pos` is the origin (start of the baseline) as well.
+    pub fn render_text(
         &mut self,
+        img: &mut image::RgbImage,
         pos: Point2<i32>,
-        img: &image::DynamicImage,
-        is_transparent: bool,
-    ) -> mxcfb_rect {
-        let rgb_img = if is_transparent {
-            self.calc_overlay_image(pos, img)
-        } else {
-            img.to_rgb()
-        };
-
-        self.framebuffer_mut().draw_image(&rgb_img, pos);
-        mxcfb_rect {
-            top: pos.y as u32,
-            left: pos.x as u32,
-            width: rgb_img.width(),
-            height: rgb_img.height(),
+        text: &str,
+        size: f32,
+    ) {
+        let scale = Scale { x: size, y: size };
+        let start = point(pos.x as f32, pos.y as f32);
+        let font = &self.framebuffer_mut().default_font;
+        for glyph in font.layout(text, scale, start) {
+            if let Some(bounding_box) = glyph.pixel_bounding_box() {
+                glyph.draw(|x, y, v| {
+                    let x = x as i32 + bounding_box.min.x;
+                    let y = y as i32 + bounding_box.min.y;
+                    if x < 0 || y < 0 || x as u32 >= img.width() || y as u32 >= img.height() {
+                        return;
+                    }
+                    let pixel = img.get_pixel_mut(x as u32, y as u32);
+                    let mult = (1.0 - v).clamp(0.0, 1.0);
+                    for channel in pixel.data.iter_mut() {
+                        *channel = (*channel as f32 * mult) as u8;
+                    }
+                });
+            }
         }
     }
 
@@ -394,3 +750,187 @@ impl<'a> Canvas<'a> {
             && (pos.y as u32) < (hitbox.top + hitbox.height)
     }
 }
+
+fn rect_area(rect: &mxcfb_rect) -> u64 {
+    rect.width as u64 * rect.height as u64
+}
+
+pub fn rects_intersect(a: &mxcfb_rect, b: &mxcfb_rect) -> bool {
+    a.left < b.left + b.width
+        && b.left < a.left + a.width
+        && a.top < b.top + b.height
+        && b.top < a.top + a.height
+}
+
+/// Merges regions into their bounding boxes as long as the bounding
+/// box doesn't contain more than `max_waste` (fraction) of pixels
+/// that are not part of the merged regions. Less refreshes mean
+/// less overhead, but refreshing unchanged pixels isn't free either.
+pub fn merge_regions(regions: &[mxcfb_rect], max_waste: f32) -> Vec<mxcfb_rect> {
+    // Regions and the amount of pixels they are covering (approximated
+    // by the sum of the merged areas, which is exact without overlaps)
+    let mut merged: Vec<(mxcfb_rect, u64)> = regions
+        .iter()
+        .filter(|region| region.width > 0 && region.height > 0)
+        .map(|region| (*region, rect_area(region)))
+        .collect();
+
+    loop {
+        // Find the pair that wastes the least when merged
+        let mut best: Option<(usize, usize, f32)> = None;
+        for i in 0..merged.len() {
+            for j in (i + 1)..merged.len() {
+                let bounding_box = merged[i].0.merge_rect(&merged[j].0);
+                let covered = (merged[i].1 + merged[j].1).min(rect_area(&bounding_box));
+                let waste = 1.0 - covered as f32 / rect_area(&bounding_box) as f32;
+                if waste <= max_waste && best.map(|(_, _, w)| waste < w).unwrap_or(true) {
+                    best = Some((i, j, waste));
+                }
+            }
+        }
+
+        match best {
+            Some((i, j, _)) => {
+                let (other, other_covered) = merged.remove(j);
+                let (region, covered) = &mut merged[i];
+                *region = region.merge_rect(&other);
+                *covered += other_covered;
+            }
+            None => break,
+        }
+    }
+
+    merged.into_iter().map(|(region, _)| region).collect()
+}
+
+/// Framebuffer that is backed by memory instead of the display.
+/// Only drawing works. Refreshing would fail.
+fn memory_framebuffer() -> Result<Framebuffer<'static>> {
+    const BYTES_PER_PIXEL: u32 = 2;
+    let (width, height) = (DISPLAYWIDTH as u32, DISPLAYHEIGHT as u32);
+    let line_length = width * BYTES_PER_PIXEL;
+
+    // The mapping stays valid after the file is removed
+    let path = std::env::temp_dir().join(format!("inky-2048-{}.fb", std::process::id()));
+    let device = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .with_context(|| format!("Failed to create {:?}", path))?;
+    device.set_len((line_length * height) as u64)?;
+    let frame = MmapOptions::new()
+        .len((line_length * height) as usize)
+        .map_raw(&device)
+        .context("Failed to map memory for the framebuffer")?;
+    fs::remove_file(&path)?;
+
+    let bitfield = |offset, length| Bitfield {
+        offset,
+        length,
+        msb_right: 0,
+    };
+    let var_screen_info = VarScreeninfo {
+        xres: width,
+        yres: height,
+        xres_virtual: width,
+        yres_virtual: height,
+        xoffset: 0,
+        yoffset: 0,
+        bits_per_pixel: BYTES_PER_PIXEL * 8,
+        grayscale: 0,
+        red: bitfield(11, 5),
+        green: bitfield(5, 6),
+        blue: bitfield(0, 5),
+        transp: bitfield(0, 0),
+        nonstd: 0,
+        activate: 0,
+        height,
+        width,
+        accel_flags: 0,
+        pixclock: 0,
+        left_margin: 0,
+        right_margin: 0,
+        upper_margin: 0,
+        lower_margin: 0,
+        hsync_len: 0,
+        vsync_len: 0,
+        sync: 0,
+        vmode: 0,
+        rotate: 0,
+        colorspace: 0,
+        reserved: [0; 4],
+    };
+    let fix_screen_info = FixScreeninfo {
+        id: [0; 16],
+        smem_start: 0,
+        smem_len: line_length * height,
+        fb_type: 0,
+        type_aux: 0,
+        visual: 0,
+        xpanstep: 0,
+        ypanstep: 0,
+        ywrapstep: 0,
+        line_length,
+        mmio_start: 0,
+        mmio_len: 0,
+        accel: 0,
+        capabilities: 0,
+        reserved: [0; 2],
+    };
+    let default_font = FontCollection::from_bytes(FONT)
+        .and_then(|fonts| fonts.into_font())
+        .context("Failed to load font")?;
+
+    Ok(Framebuffer {
+        device,
+        frame,
+        marker: AtomicU32::new(1),
+        default_font,
+        var_screen_info,
+        fix_screen_info,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(left: u32, top: u32, width: u32, height: u32) -> mxcfb_rect {
+        mxcfb_rect {
+            top,
+            left,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn merges_neighboring_regions() {
+        let regions = [rect(0, 0, 10, 10), rect(10, 0, 10, 10), rect(0, 10, 20, 10)];
+        assert_eq!(merge_regions(&regions, 0.0), vec![rect(0, 0, 20, 20)]);
+    }
+
+    #[test]
+    fn keeps_distant_regions_apart() {
+        let regions = [rect(0, 0, 10, 10), rect(100, 100, 10, 10)];
+        assert_eq!(merge_regions(&regions, 0.5), regions.to_vec());
+        assert_eq!(merge_regions(&regions, 1.0), vec![rect(0, 0, 110, 110)]);
+    }
+
+    #[test]
+    fn merges_up_to_the_allowed_waste() {
+        // The bounding box is a quarter empty
+        let regions = [rect(0, 0, 20, 10), rect(0, 10, 10, 10)];
+        assert_eq!(merge_regions(&regions, 0.2), regions.to_vec());
+        assert_eq!(merge_regions(&regions, 0.25), vec![rect(0, 0, 20, 20)]);
+    }
+
+    #[test]
+    fn drops_empty_regions() {
+        let regions = [rect(0, 0, 0, 10), rect(5, 5, 10, 10), rect(50, 50, 10, 0)];
+        assert_eq!(merge_regions(&regions, 0.0), vec![rect(5, 5, 10, 10)]);
+        assert_eq!(merge_regions(&[], 0.0), vec![]);
+    }
+}
@@ -0,0 +1,174 @@
+use crate::board_code::Board;
+use crate::canvas::Orientation;
+use crate::config::Config;
+use crate::dither::Dithering;
+use crate::export::Format;
+use crate::game::{Mode, MAX_BOARD_SIZE, MIN_BOARD_SIZE};
+use crate::rules::{MAX_BASE, MIN_BASE};
+use crate::tiles::Theme;
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+/// 2048 for the reMarkable
+#[derive(Debug, Parser)]
+#[clap(name = "inky-2048", version)]
+pub struct Cli {
+    /// Plays a game if omitted
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Play a game
+    Play(PlayArgs),
+    /// Show a recorded game move by move
+    Replay(ReplayArgs),
+    /// Dump all recorded games including their moves
+    Export(ExportArgs),
+    /// Print statistics about all recorded games
+    Stats,
+    /// Measure how long rendering tiles and playing takes
+    Bench(BenchArgs),
+    /// Draw a board once, on the screen or into a PNG
+    Render(RenderArgs),
+    /// Print the config as used, including all defaults
+    Config(ConfigArgs),
+}
+
+#[derive(Debug, Default, Args)]
+pub struct PlayArgs {
+    #[clap(flatten)]
+    pub game: GameArgs,
+    #[clap(flatten)]
+    pub display: DisplayArgs,
+}
+
+/// How the (first) game starts. Mode and size override the config file.
+#[derive(Debug, Default, Args)]
+pub struct GameArgs {
+    /// Seed for the spawned tiles. Random if omitted.
+    #[clap(long)]
+    pub seed: Option<u64>,
+    /// Width and height of the board
+    #[clap(long, value_parser = parse_board_size)]
+    pub size: Option<usize>,
+    #[clap(long, value_enum)]
+    pub mode: Option<Mode>,
+    /// Equal tiles it takes to merge in custom-base games
+    #[clap(long, value_parser = parse_base)]
+    pub base: Option<u32>,
+    /// Walls placed at random in obstacle games
+    #[clap(long)]
+    pub walls: Option<usize>,
+    /// Minutes to play in time attack games
+    #[clap(long, value_name = "MINUTES")]
+    pub time_limit: Option<u64>,
+    /// Start from a board code as logged at game over. Walls
+    /// can be put anywhere with an X.
+    #[clap(long, value_parser = Board::decode)]
+    pub board: Option<Board>,
+}
+
+/// How things look. Overrides the config file.
+#[derive(Debug, Default, Args)]
+pub struct DisplayArgs {
+    #[clap(long, value_enum)]
+    pub theme: Option<Theme>,
+    #[clap(long, value_enum)]
+    pub dithering: Option<Dithering>,
+    #[clap(long, value_enum)]
+    pub orientation: Option<Orientation>,
+}
+
+impl GameArgs {
+    pub fn apply(&self, config: &mut Config) {
+        config.mode = self.mode.unwrap_or(config.mode);
+        config.base = self.base.unwrap_or(config.base);
+        config.walls = self.walls.unwrap_or(config.walls);
+        config.time_limit_minutes = self.time_limit.unwrap_or(config.time_limit_minutes);
+        config.board_size = self
+            .size
+            .or_else(|| self.board.as_ref().map(|board| board.size))
+            .unwrap_or(config.board_size);
+    }
+}
+
+impl DisplayArgs {
+    pub fn apply(&self, config: &mut Config) {
+        config.theme = self.theme.unwrap_or(config.theme);
+        config.dithering = self.dithering.unwrap_or(config.dithering);
+        config.orientation = self.orientation.unwrap_or(config.orientation);
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ReplayArgs {
+    /// A JSON export, a single exported game or a games.jsonl
+    pub file: PathBuf,
+    /// Index of the game within the file. The last one if omitted.
+    #[clap(long)]
+    pub game: Option<usize>,
+    /// Time between two moves
+    #[clap(long, default_value_t = 700)]
+    pub delay_ms: u64,
+    #[clap(flatten)]
+    pub display: DisplayArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    #[clap(long, value_enum, default_value_t = Format::Json)]
+    pub format: Format,
+    /// Writes to stdout if omitted
+    #[clap(long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct BenchArgs {
+    /// Amount of games played randomly to measure the game logic
+    #[clap(long, default_value_t = 200)]
+    pub games: u32,
+    #[clap(flatten)]
+    pub display: DisplayArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct RenderArgs {
+    /// Draw into this PNG instead of onto the screen
+    #[clap(long, value_name = "OUT.png")]
+    pub headless: Option<PathBuf>,
+    #[clap(flatten)]
+    pub game: GameArgs,
+    #[clap(flatten)]
+    pub display: DisplayArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigArgs {
+    /// Write it to the config file as well
+    #[clap(long)]
+    pub save: bool,
+}
+
+fn parse_board_size(size: &str) -> Result<usize, String> {
+    let size: usize = size.parse().map_err(|e| format!("{}", e))?;
+    if (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&size) {
+        Ok(size)
+    } else {
+        Err(format!(
+            "Boards need to be {} to {} cells wide",
+            MIN_BOARD_SIZE, MAX_BOARD_SIZE
+        ))
+    }
+}
+
+fn parse_base(base: &str) -> Result<u32, String> {
+    let base: u32 = base.parse().map_err(|e| format!("{}", e))?;
+    if (MIN_BASE..=MAX_BASE).contains(&base) {
+        Ok(base)
+    } else {
+        Err(format!("The base needs to be {} to {}", MIN_BASE, MAX_BASE))
+    }
+}
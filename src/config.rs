@@ -0,0 +1,464 @@
+use crate::board_view::RefreshPolicy;
+use crate::canvas::Orientation;
+use crate::dither::Dithering;
+use crate::game::{
+    Mode, DEFAULT_BOARD_SIZE, DEFAULT_WALLS, MAX_BOARD_SIZE, MAX_WALLS, MIN_BOARD_SIZE,
+};
+use crate::layout::Handedness;
+use crate::move_queue::QueuePolicy;
+use crate::rules::{Rules, DEFAULT_BASE, MAX_BASE, MIN_BASE};
+use crate::stats;
+use crate::swipe::SwipeSettings;
+use crate::tiles::Theme;
+use crate::timer::{DEFAULT_TIME_LIMIT_MINUTES, MAX_TIME_LIMIT_MINUTES};
+use anyhow::{Context, Result};
+use libremarkable::input::gpio::PhysicalButton;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use std::{env, thread};
+use toml::value::Table;
+
+const CONFIG_FILE: &str = "config.toml";
+/// How often the config file is checked for changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+/// Minimum time between a move and spawning the new tile.
+/// The refreshes of the move will be waited for in any case.
+const DEFAULT_SPAWN_DELAY_MS: u64 = 100;
+/// Longer delays would make the game feel stuck.
+const MAX_SPAWN_DELAY_MS: u64 = 2000;
+
+/// Everything that can be set in CONFIG_FILE. Missing keys fall back
+/// to the INKY_2048_* environment variables and then to the defaults.
+/// Command line options take precedence over both.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    #[serde(with = "value_enum")]
+    pub theme: Theme,
+    #[serde(with = "value_enum")]
+    pub dithering: Dithering,
+    #[serde(with = "value_enum")]
+    pub orientation: Orientation,
+    #[serde(with = "value_enum")]
+    pub handedness: Handedness,
+    /// Mode and size of new games
+    #[serde(with = "value_enum")]
+    pub mode: Mode,
+    pub board_size: usize,
+    /// Equal tiles it takes to merge in custom-base games
+    pub base: u32,
+    /// Walls placed at random in obstacle games
+    pub walls: usize,
+    /// Time to play in time attack games
+    pub time_limit_minutes: u64,
+    /// Let new tiles grow into their cells
+    pub animations: bool,
+    #[serde(with = "value_enum")]
+    pub refresh: RefreshPolicy,
+    pub spawn_delay_ms: u64,
+    #[serde(with = "value_enum")]
+    pub move_queue: QueuePolicy,
+    // Tables have to come last in TOML
+    pub swipe: SwipeSettings,
+    pub keys: Keymap,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            theme: Theme::from_env(),
+            dithering: Dithering::from_env(),
+            orientation: Orientation::Portrait,
+            handedness: Handedness::Right,
+            mode: Mode::Classic,
+            board_size: DEFAULT_BOARD_SIZE,
+            base: DEFAULT_BASE,
+            walls: DEFAULT_WALLS,
+            time_limit_minutes: DEFAULT_TIME_LIMIT_MINUTES,
+            animations: true,
+            refresh: RefreshPolicy::Normal,
+            spawn_delay_ms: spawn_delay_ms_from_env(),
+            move_queue: QueuePolicy::from_env(),
+            swipe: SwipeSettings::default(),
+            keys: Keymap::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads and validates the config file. It's fine if there is none.
+    pub fn load() -> Result<Self> {
+        let path = path();
+        let config: Self = match fs::read_to_string(&path) {
+            Ok(content) => {
+                toml::from_str(&content).with_context(|| format!("Invalid config {:?}", path))?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+        };
+        config
+            .validate()
+            .with_context(|| format!("Invalid config {:?}", path))?;
+        Ok(config)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        self.validate()?;
+        let path = path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+        }
+        fs::write(&path, toml::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    /// Changes the config file with `edit`. Only the keys that `edit`
+    /// changed are written and the rest of the file stays as it is,
+    /// so that INKY_2048_* variables don't end up in there.
+    pub fn edit_file(edit: impl FnOnce(&mut Self)) -> Result<()> {
+        let before = Self::load()?;
+        let mut after = before;
+        edit(&mut after);
+        after.validate()?;
+
+        let path = path();
+        let mut file: Table = match fs::read_to_string(&path) {
+            Ok(content) => {
+                toml::from_str(&content).with_context(|| format!("Invalid config {:?}", path))?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Table::new(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+        };
+        write_changes(&mut file, before, after)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+        }
+        fs::write(&path, toml::to_string_pretty(&file)?)
+            .with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    /// Checks values that can't be ruled out by their types alone.
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            (MIN_BOARD_SIZE..=MAX_BOARD_SIZE).contains(&self.board_size),
+            "board-size needs to be {} to {}",
+            MIN_BOARD_SIZE,
+            MAX_BOARD_SIZE
+        );
+        ensure!(
+            (MIN_BASE..=MAX_BASE).contains(&self.base),
+            "base needs to be {} to {}",
+            MIN_BASE,
+            MAX_BASE
+        );
+        ensure!(
+            self.mode != Mode::CustomBase || self.base as usize <= self.board_size,
+            "base can't be larger than board-size in custom-base games"
+        );
+        ensure!(
+            self.walls <= MAX_WALLS,
+            "walls can be at most {}",
+            MAX_WALLS
+        );
+        ensure!(
+            (1..=MAX_TIME_LIMIT_MINUTES).contains(&self.time_limit_minutes),
+            "time-limit-minutes needs to be 1 to {}",
+            MAX_TIME_LIMIT_MINUTES
+        );
+        ensure!(
+            self.spawn_delay_ms <= MAX_SPAWN_DELAY_MS,
+            "spawn-delay-ms can be at most {}",
+            MAX_SPAWN_DELAY_MS
+        );
+        self.swipe.validate().context("Invalid [swipe]")
+    }
+
+    /// Rules of new games.
+    pub fn rules(&self) -> Rules {
+        self.mode.rules(self.base)
+    }
+
+    /// Walls of new games. Only obstacle games have any.
+    pub fn start_walls(&self) -> usize {
+        match self.mode {
+            Mode::Obstacles => self.walls,
+            _ => 0,
+        }
+    }
+
+    /// Time limit of new games. Only time attack games have one.
+    pub fn time_limit(&self) -> Option<Duration> {
+        match self.mode {
+            Mode::TimeAttack => Some(Duration::from_secs(self.time_limit_minutes * 60)),
+            _ => None,
+        }
+    }
+
+    pub fn spawn_delay(&self) -> Duration {
+        Duration::from_millis(self.spawn_delay_ms)
+    }
+}
+
+/// Puts the keys whose values differ between `before` and `after`
+/// into `file`.
+fn write_changes(file: &mut Table, before: Config, after: Config) -> Result<()> {
+    let before: Table = toml::Value::try_from(before)?.try_into()?;
+    let after: Table = toml::Value::try_from(after)?.try_into()?;
+    for (key, value) in after {
+        if before.get(&key) != Some(&value) {
+            file.insert(key, value);
+        }
+    }
+    Ok(())
+}
+
+/// Location of the config file.
+pub fn path() -> PathBuf {
+    stats::home_dir()
+        .join(".config/inky-2048")
+        .join(CONFIG_FILE)
+}
+
+/// Calls `on_change` whenever the config file changed and is
+/// still valid. Invalid changes are logged and ignored.
+pub fn watch(on_change: impl Fn(Config) + Send + 'static) {
+    thread::spawn(move || {
+        let path = path();
+        let mut last_modified = modified(&path);
+        loop {
+            thread::sleep(WATCH_INTERVAL);
+            let modified = modified(&path);
+            if modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+            match Config::load() {
+                Ok(config) => {
+                    info!("Reloaded {:?}", path);
+                    on_change(config);
+                }
+                Err(e) => error!("Keeping the previous config: {:?}", e),
+            }
+        }
+    });
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Reads the minimum time between a move and the spawning of the
+/// new tile from INKY_2048_SPAWN_DELAY_MS.
+fn spawn_delay_ms_from_env() -> u64 {
+    match env::var("INKY_2048_SPAWN_DELAY_MS").map(|ms| ms.parse::<u64>()) {
+        Ok(Ok(ms)) => ms,
+        Ok(Err(e)) => {
+            warn!("Invalid INKY_2048_SPAWN_DELAY_MS ({}). Using default.", e);
+            DEFAULT_SPAWN_DELAY_MS
+        }
+        Err(_) => DEFAULT_SPAWN_DELAY_MS,
+    }
+}
+
+/// What a hardware button does when pressed.
+#[derive(Debug, Copy, Clone, PartialEq, clap::ValueEnum)]
+pub enum Action {
+    None,
+    /// Shows the start screen
+    Menu,
+    NewGame,
+    Screenshot,
+    Quit,
+}
+
+/// Actions of the hardware buttons. Only the reMarkable 1 has
+/// more than a power button.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Keymap {
+    #[serde(with = "value_enum")]
+    pub left: Action,
+    #[serde(with = "value_enum")]
+    pub middle: Action,
+    #[serde(with = "value_enum")]
+    pub right: Action,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            left: Action::Menu,
+            middle: Action::Quit,
+            right: Action::Screenshot,
+        }
+    }
+}
+
+impl Keymap {
+    pub fn action(&self, button: PhysicalButton) -> Action {
+        match button {
+            PhysicalButton::LEFT => self.left,
+            PhysicalButton::MIDDLE => self.middle,
+            PhysicalButton::RIGHT => self.right,
+            // Left to the system
+            PhysicalButton::POWER | PhysicalButton::WAKEUP => Action::None,
+        }
+    }
+}
+
+/// (De)serializes enums by the same names as used on the command line.
+pub mod value_enum {
+    use clap::ValueEnum;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<T: ValueEnum, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value.to_possible_value() {
+            Some(possible_value) => serializer.serialize_str(possible_value.get_name()),
+            None => Err(serde::ser::Error::custom("Value can't be named")),
+        }
+    }
+
+    pub fn deserialize<'de, T: ValueEnum, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        T::from_str(&name, true).map_err(|_| {
+            let names: Vec<&str> = T::value_variants()
+                .iter()
+                .filter_map(|value| value.to_possible_value())
+                .map(|possible_value| possible_value.get_name())
+                .collect();
+            D::Error::custom(format!(
+                "Unknown value {:?}, expected one of {}",
+                name,
+                names.join(", ")
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_the_defaults() {
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_values_out_of_range() {
+        let config = Config::default();
+        for board_size in [MIN_BOARD_SIZE - 1, MAX_BOARD_SIZE + 1] {
+            assert!(Config {
+                board_size,
+                ..config
+            }
+            .validate()
+            .is_err());
+        }
+        let spawn_delay_ms = MAX_SPAWN_DELAY_MS + 1;
+        assert!(Config {
+            spawn_delay_ms,
+            ..config
+        }
+        .validate()
+        .is_err());
+        let swipe = SwipeSettings {
+            min_ratio: 0.5,
+            ..config.swipe
+        };
+        assert!(Config { swipe, ..config }.validate().is_err());
+    }
+
+    #[test]
+    fn fits_the_base_into_the_board() {
+        let config = Config {
+            mode: Mode::CustomBase,
+            board_size: 4,
+            ..Config::default()
+        };
+        assert!(Config { base: 4, ..config }.validate().is_ok());
+        assert!(Config { base: 5, ..config }.validate().is_err());
+        assert!(Config { base: 1, ..config }.validate().is_err());
+        // Only custom-base games use it
+        let classic = Config {
+            mode: Mode::Classic,
+            ..config
+        };
+        assert!(Config { base: 5, ..classic }.validate().is_ok());
+    }
+
+    #[test]
+    fn places_walls_in_obstacle_games_only() {
+        let config = Config {
+            walls: 3,
+            ..Config::default()
+        };
+        assert_eq!(config.start_walls(), 0);
+        let obstacles = Config {
+            mode: Mode::Obstacles,
+            ..config
+        };
+        assert_eq!(obstacles.start_walls(), 3);
+        let walls = MAX_WALLS + 1;
+        assert!(Config { walls, ..obstacles }.validate().is_err());
+    }
+
+    #[test]
+    fn names_enums_as_on_the_command_line() {
+        let config = Config {
+            orientation: Orientation::Landscape,
+            dithering: Dithering::FloydSteinberg,
+            move_queue: QueuePolicy::DropWhileBusy,
+            keys: Keymap {
+                left: Action::NewGame,
+                ..Keymap::default()
+            },
+            ..Config::default()
+        };
+        let toml = toml::to_string_pretty(&config).unwrap();
+        assert!(toml.contains("orientation = 'landscape'"));
+        assert!(toml.contains("dithering = 'floyd-steinberg'"));
+        assert!(toml.contains("move-queue = 'drop'"));
+        assert!(toml.contains("left = 'new-game'"));
+        assert_eq!(toml::from_str::<Config>(&toml).unwrap(), config);
+    }
+
+    #[test]
+    fn fills_in_missing_keys() {
+        let config: Config = toml::from_str("board-size = 5\n[keys]\nright = \"quit\"").unwrap();
+        assert_eq!(config.board_size, 5);
+        assert_eq!(config.keys.right, Action::Quit);
+        assert_eq!(config.keys.left, Keymap::default().left);
+        assert_eq!(config.mode, Config::default().mode);
+    }
+
+    #[test]
+    fn rejects_unknown_names() {
+        let e = toml::from_str::<Config>("mode = \"chess\"").unwrap_err();
+        assert!(e.to_string().contains("expected one of classic"), "{}", e);
+        assert!(toml::from_str::<Config>("colour = \"red\"").is_err());
+    }
+
+    #[test]
+    fn writes_only_changed_keys() {
+        let mut file: Table = toml::from_str("refresh = 'fast'").unwrap();
+        let before = Config::default();
+        let mut after = before;
+        after.animations = false;
+        after.swipe.chunk_distance = 35;
+        write_changes(&mut file, before, after).unwrap();
+        let keys: Vec<&str> = file.keys().map(String::as_str).collect();
+        assert_eq!(keys, ["animations", "refresh", "swipe"]);
+        let config: Config = toml::from_str(&toml::to_string(&file).unwrap()).unwrap();
+        assert!(!config.animations);
+        assert_eq!(config.swipe.chunk_distance, 35);
+    }
+}
@@ -0,0 +1,171 @@
+use libremarkable::image::{Rgb, RgbImage};
+use std::env;
+
+/// 4x4 Bayer matrix for ordered dithering.
+#[rustfmt::skip]
+const BAYER_4X4: [[u8; 4]; 4] = [
+    [ 0,  8,  2, 10],
+    [12,  4, 14,  6],
+    [ 3, 11,  1,  9],
+    [15,  7, 13,  5],
+];
+
+/// How images get converted to pure black and white before being
+/// drawn. The DU and A2 waveforms can only display black and white,
+/// so anything gray would otherwise be left to the display controller.
+#[derive(Debug, Copy, Clone, PartialEq, clap::ValueEnum)]
+pub enum Dithering {
+    /// Keep the images as they are. The shipped art is already
+    /// dithered (blue noise), so this is the default.
+    None,
+    /// Every pixel becomes black or white depending on its brightness.
+    Threshold,
+    /// Ordered dithering using a 4x4 Bayer matrix.
+    Bayer,
+    /// Error diffusion after Floyd and Steinberg.
+    FloydSteinberg,
+}
+
+impl Dithering {
+    /// Reads the dithering from INKY_2048_DITHERING
+    /// (none, threshold, bayer or floyd-steinberg).
+    pub fn from_env() -> Self {
+        match env::var("INKY_2048_DITHERING").as_deref() {
+            Ok("none") | Err(_) => Dithering::None,
+            Ok("threshold") => Dithering::Threshold,
+            Ok("bayer") => Dithering::Bayer,
+            Ok("floyd-steinberg") => Dithering::FloydSteinberg,
+            Ok(other) => {
+                warn!("Unknown dithering {:?}. Using none.", other);
+                Dithering::None
+            }
+        }
+    }
+
+    /// Whether the images should be made from the original
+    /// (not yet dithered) art.
+    pub fn wants_original_art(self) -> bool {
+        self != Dithering::None
+    }
+
+    pub fn apply(self, img: &mut RgbImage) {
+        match self {
+            Dithering::None => {}
+            Dithering::Threshold => {
+                for pixel in img.pixels_mut() {
+                    *pixel = black_or_white(luma(pixel) > 127);
+                }
+            }
+            Dithering::Bayer => {
+                for (x, y, pixel) in img.enumerate_pixels_mut() {
+                    // Map the matrix entry to the middle of its 1/16 step
+                    let threshold = BAYER_4X4[y as usize % 4][x as usize % 4] as u16 * 16 + 8;
+                    *pixel = black_or_white(luma(pixel) as u16 > threshold);
+                }
+            }
+            Dithering::FloydSteinberg => floyd_steinberg(img),
+        }
+    }
+}
+
+fn luma(pixel: &Rgb<u8>) -> u8 {
+    ((pixel[0] as u16 * 77 + pixel[1] as u16 * 150 + pixel[2] as u16 * 29) >> 8) as u8
+}
+
+fn black_or_white(white: bool) -> Rgb<u8> {
+    if white {
+        Rgb([255, 255, 255])
+    } else {
+        Rgb([0, 0, 0])
+    }
+}
+
+fn floyd_steinberg(img: &mut RgbImage) {
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let mut lumas: Vec<i16> = img.pixels().map(|pixel| luma(pixel) as i16).collect();
+
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            let white = lumas[i] > 127;
+            let error = lumas[i] - if white { 255 } else { 0 };
+            img.put_pixel(x as u32, y as u32, black_or_white(white));
+
+            if x + 1 < width {
+                lumas[i + 1] += error * 7 / 16;
+            }
+            if y + 1 < height {
+                if x > 0 {
+                    lumas[i + width - 1] += error * 3 / 16;
+                }
+                lumas[i + width] += error * 5 / 16;
+                if x + 1 < width {
+                    lumas[i + width + 1] += error / 16;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(value: u8, size: u32) -> RgbImage {
+        RgbImage::from_pixel(size, size, Rgb([value, value, value]))
+    }
+
+    fn white_pixels(img: &RgbImage) -> usize {
+        img.pixels().filter(|pixel| pixel[0] == 255).count()
+    }
+
+    fn is_black_and_white(img: &RgbImage) -> bool {
+        img.pixels()
+            .all(|pixel| *pixel == Rgb([0, 0, 0]) || *pixel == Rgb([255, 255, 255]))
+    }
+
+    #[test]
+    fn none_keeps_the_image() {
+        let mut img = gray(100, 8);
+        Dithering::None.apply(&mut img);
+        assert_eq!(img.into_raw(), gray(100, 8).into_raw());
+    }
+
+    #[test]
+    fn threshold_splits_at_half_brightness() {
+        let mut dark = gray(127, 4);
+        Dithering::Threshold.apply(&mut dark);
+        assert_eq!(white_pixels(&dark), 0);
+        assert!(is_black_and_white(&dark));
+        let mut light = gray(128, 4);
+        Dithering::Threshold.apply(&mut light);
+        assert_eq!(white_pixels(&light), 16);
+        assert!(is_black_and_white(&light));
+    }
+
+    #[test]
+    fn bayer_keeps_the_brightness() {
+        for (value, white) in [(0, 0), (64, 4), (128, 8), (192, 12), (255, 16)] {
+            let mut img = gray(value, 4);
+            Dithering::Bayer.apply(&mut img);
+            assert!(is_black_and_white(&img));
+            assert_eq!(white_pixels(&img), white, "Gray {}", value);
+        }
+    }
+
+    #[test]
+    fn floyd_steinberg_keeps_the_brightness() {
+        for value in [0, 64, 128, 192, 255] {
+            let mut img = gray(value, 32);
+            Dithering::FloydSteinberg.apply(&mut img);
+            assert!(is_black_and_white(&img));
+            let brightness = white_pixels(&img) as f32 * 255.0 / 1024.0;
+            assert!(
+                (brightness - value as f32).abs() < 8.0,
+                "Gray {} became {}",
+                value,
+                brightness
+            );
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use crate::cli::ExportArgs;
+use crate::stats::{self, GameRecord};
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+#[derive(Debug, Copy, Clone, PartialEq, clap::ValueEnum)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+/// Dumps all recorded games including their moves.
+/// Writes to stdout unless `--output` is given.
+pub fn run(args: &ExportArgs) -> Result<()> {
+    let records = stats::load_games()?;
+    let writer: Box<dyn Write> = match &args.output {
+        Some(path) => {
+            Box::new(File::create(path).with_context(|| format!("Failed to create {:?}", path))?)
+        }
+        None => Box::new(io::stdout()),
+    };
+    let mut writer = BufWriter::new(writer);
+    match args.format {
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut writer, &records)?;
+            writeln!(writer)?;
+        }
+        Format::Csv => write_csv(&mut writer, &records)?,
+    }
+    writer.flush()?;
+    info!("Exported {} games as {:?}", records.len(), args.format);
+    Ok(())
+}
+
+/// One row per move, repeating the columns of the game. Games
+/// without stored moves get a single row with empty move columns.
+fn write_csv(writer: &mut impl Write, records: &[GameRecord]) -> Result<()> {
+    writeln!(
+        writer,
+        "game,finished_at,mode,board_size,seed,score,max_tile,moves,duration_secs,\
+         move,direction,score_delta,spawn_x,spawn_y,spawn_value"
+    )?;
+    for (game, record) in records.iter().enumerate() {
+        let game_columns = format!(
+            "{},{},{},{},{},{},{},{},{}",
+            game,
+            record.finished_at,
+            record.mode_name(),
+            record.board_size,
+            record.seed,
+            record.score,
+            record.max_tile,
+            record.moves,
+            record.duration_secs
+        );
+        if record.move_list.is_empty() {
+            writeln!(writer, "{},,,,,,", game_columns)?;
+        }
+        for (i, move_record) in record.move_list.iter().enumerate() {
+            let spawn_columns = match move_record.spawned {
+                Some(tile) => format!("{},{},{}", tile.x, tile.y, tile.value),
+                None => ",,".to_owned(),
+            };
+            writeln!(
+                writer,
+                "{},{},{:?},{},{}",
+                game_columns, i, move_record.direction, move_record.score_delta, spawn_columns
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Direction, Game, Mode, MoveRecord, SpawnedTile};
+    use crate::rules::Rules;
+
+    fn record(moves: Vec<MoveRecord>) -> GameRecord {
+        let mut record = GameRecord::from_game(&Game::new(
+            Mode::Classic,
+            Rules::Powers { base: 2 },
+            4,
+            7,
+            0,
+        ));
+        record.finished_at = 1700000000;
+        record.duration_secs = 42;
+        record.score = 12;
+        record.max_tile = 8;
+        record.moves = moves.len() as u32;
+        record.move_list = moves;
+        record
+    }
+
+    fn csv(records: &[GameRecord]) -> Vec<String> {
+        let mut out = Vec::new();
+        write_csv(&mut out, records).unwrap();
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(str::to_owned)
+            .collect()
+    }
+
+    #[test]
+    fn writes_a_row_per_move() {
+        let moves = vec![
+            MoveRecord {
+                direction: Direction::Left,
+                score_delta: 4,
+                spawned: Some(SpawnedTile {
+                    x: 3,
+                    y: 0,
+                    value: 2,
+                }),
+                removed: None,
+            },
+            MoveRecord {
+                direction: Direction::Up,
+                score_delta: 8,
+                spawned: None,
+                removed: None,
+            },
+        ];
+        let rows = csv(&[record(moves)]);
+        assert_eq!(rows.len(), 3);
+        assert!(rows[0].starts_with("game,finished_at,mode,"));
+        assert_eq!(rows[1], "0,1700000000,Classic,4,7,12,8,2,42,0,Left,4,3,0,2");
+        assert_eq!(rows[2], "0,1700000000,Classic,4,7,12,8,2,42,1,Up,8,,,");
+    }
+
+    #[test]
+    fn writes_a_row_for_games_without_moves() {
+        let rows = csv(&[record(Vec::new()), record(Vec::new())]);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1], "0,1700000000,Classic,4,7,12,8,0,42,,,,,,");
+        assert_eq!(rows[2], "1,1700000000,Classic,4,7,12,8,0,42,,,,,,");
+        // Every row has all the columns
+        let columns = rows[0].split(',').count();
+        assert!(rows.iter().all(|row| row.split(',').count() == columns));
+    }
+}
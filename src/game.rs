@@ -0,0 +1,691 @@
+use crate::rules::Rules;
+use crate::timer::Clock;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+pub const DEFAULT_BOARD_SIZE: usize = 4;
+pub const MIN_BOARD_SIZE: usize = 3;
+pub const MAX_BOARD_SIZE: usize = 8;
+pub const DEFAULT_WALLS: usize = 2;
+/// Even the smallest board keeps most of its cells free.
+pub const MAX_WALLS: usize = 4;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, clap::ValueEnum)]
+pub enum Mode {
+    /// Equal tiles merge
+    Classic,
+    /// Neighbors in the Fibonacci sequence merge (1, 2, 3, 5, ..)
+    Fibonacci,
+    /// Three equal tiles in a row merge (3, 9, 27, ..)
+    PowersOfThree,
+    /// As many equal tiles in a row as the configured base merge
+    CustomBase,
+    /// Classic with walls in the way
+    Obstacles,
+    /// Classic against the clock
+    TimeAttack,
+    /// Classic without score or game over
+    Zen,
+}
+
+impl Mode {
+    /// `base` is only used for custom-base games.
+    pub fn rules(self, base: u32) -> Rules {
+        match self {
+            Mode::Classic => Rules::Powers { base: 2 },
+            Mode::Fibonacci => Rules::Fibonacci,
+            Mode::PowersOfThree => Rules::Powers { base: 3 },
+            Mode::CustomBase => Rules::Powers { base },
+            Mode::Obstacles | Mode::TimeAttack | Mode::Zen => Rules::Powers { base: 2 },
+        }
+    }
+
+    /// Zen games are played for the tiles alone.
+    pub fn shows_score(self) -> bool {
+        self != Mode::Zen
+    }
+}
+
+/// What a cell of the board holds.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Cell {
+    Empty,
+    /// A tile with its value
+    Tile(u32),
+    /// Tiles can neither pass nor merge with it
+    Wall,
+}
+
+impl Cell {
+    /// Value of the tile. None for empty cells and walls.
+    pub fn tile(self) -> Option<u32> {
+        match self {
+            Cell::Tile(value) => Some(value),
+            Cell::Empty | Cell::Wall => None,
+        }
+    }
+}
+
+/// A tile that appeared on the board.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpawnedTile {
+    pub x: usize,
+    pub y: usize,
+    pub value: u32,
+}
+
+/// A move that changed the board.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MoveRecord {
+    pub direction: Direction,
+    /// Points gained by merges
+    pub score_delta: u32,
+    /// Tile that appeared after the move
+    pub spawned: Option<SpawnedTile>,
+    /// Tile taken off the board after the spawn in zen games
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub removed: Option<SpawnedTile>,
+}
+
+/// The state of a single game. Cells are stored row by row.
+pub struct Game {
+    pub mode: Mode,
+    pub rules: Rules,
+    pub size: usize,
+    pub seed: u64,
+    pub score: u32,
+    pub moves: u32,
+    /// Tiles the game started with
+    pub start_tiles: Vec<SpawnedTile>,
+    pub history: Vec<MoveRecord>,
+    /// Time to play in time attack games
+    pub time_limit: Option<Duration>,
+    /// Tiles still to spawn in puzzles. Nothing spawns once it's
+    /// empty. Spawned tiles are random if there is none.
+    pub spawns: Option<VecDeque<u32>>,
+    /// Time the game was played for. Runs while its board is shown.
+    pub clock: Clock,
+    cells: Vec<Cell>,
+    rng: StdRng,
+}
+
+impl Game {
+    /// Starts a new game with a single tile on the board. The given
+    /// number of walls gets placed at random before.
+    pub fn new(mode: Mode, rules: Rules, size: usize, seed: u64, walls: usize) -> Self {
+        let mut game = Self {
+            mode,
+            rules,
+            size,
+            seed,
+            score: 0,
+            moves: 0,
+            start_tiles: Vec::new(),
+            history: Vec::new(),
+            time_limit: None,
+            spawns: None,
+            clock: Clock::new(),
+            cells: vec![Cell::Empty; size * size],
+            rng: StdRng::seed_from_u64(seed),
+        };
+        for _ in 0..walls {
+            if let Some(index) = game.random_empty_cell() {
+                game.cells[index] = Cell::Wall;
+            }
+        }
+        game.spawn_tile();
+        game
+    }
+
+    /// Continues from the given position. All tiles count as
+    /// start tiles.
+    pub fn with_cells(
+        mode: Mode,
+        rules: Rules,
+        size: usize,
+        seed: u64,
+        cells: Vec<Cell>,
+        score: u32,
+    ) -> Self {
+        assert_eq!(cells.len(), size * size, "Cells don't match the board size");
+        let start_tiles = cells
+            .iter()
+            .enumerate()
+            .filter_map(|(i, cell)| {
+                Some(SpawnedTile {
+                    x: i % size,
+                    y: i / size,
+                    value: cell.tile()?,
+                })
+            })
+            .collect();
+        Self {
+            mode,
+            rules,
+            size,
+            seed,
+            score,
+            moves: 0,
+            start_tiles,
+            history: Vec::new(),
+            time_limit: None,
+            spawns: None,
+            clock: Clock::new(),
+            cells,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn cells(&self) -> &[Cell] {
+        &self.cells
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Cell {
+        self.cells[y * self.size + x]
+    }
+
+    /// Positions (x, y) of the walls. They never move.
+    pub fn walls(&self) -> Vec<(usize, usize)> {
+        (0..self.cells.len())
+            .filter(|i| self.cells[*i] == Cell::Wall)
+            .map(|i| (i % self.size, i / self.size))
+            .collect()
+    }
+
+    pub fn max_tile(&self) -> u32 {
+        self.cells
+            .iter()
+            .filter_map(|cell| cell.tile())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Time played. Time attack games end with the time limit.
+    pub fn duration(&self) -> Duration {
+        let played = self.clock.elapsed();
+        self.time_limit.map_or(played, |limit| played.min(limit))
+    }
+
+    /// Time left in time attack games.
+    pub fn time_left(&self) -> Option<Duration> {
+        self.time_limit
+            .map(|limit| limit.saturating_sub(self.clock.elapsed()))
+    }
+
+    pub fn is_time_up(&self) -> bool {
+        self.time_left().is_some_and(|left| left.is_zero())
+    }
+
+    /// Moves all tiles into the given direction. Returns false
+    /// if nothing moved, which doesn't count as a move.
+    pub fn play(&mut self, direction: Direction) -> bool {
+        let score_before = self.score;
+        let mut moved = false;
+        for line in 0..self.size {
+            for segment in self.segments(direction, line) {
+                let (merged, points) = self.merge_segment(&segment);
+                self.score += points;
+                for (i, cell) in segment.iter().zip(merged) {
+                    if self.cells[*i] != cell {
+                        self.cells[*i] = cell;
+                        moved = true;
+                    }
+                }
+            }
+        }
+        if moved {
+            self.moves += 1;
+            self.history.push(MoveRecord {
+                direction,
+                score_delta: self.score - score_before,
+                spawned: None,
+                removed: None,
+            });
+        }
+        moved
+    }
+
+    /// Indices of the cells of a row or column split at the walls,
+    /// as tiles only move between them.
+    fn segments(&self, direction: Direction, line: usize) -> Vec<Vec<usize>> {
+        self.line_indices(direction, line)
+            .split(|i| self.cells[*i] == Cell::Wall)
+            .map(|segment| segment.to_vec())
+            .collect()
+    }
+
+    /// The cells of the segment after moving all of its tiles to
+    /// the front, with the points of the merges.
+    fn merge_segment(&self, segment: &[usize]) -> (Vec<Cell>, u32) {
+        let values: Vec<u32> = segment
+            .iter()
+            .filter_map(|i| self.cells[*i].tile())
+            .collect();
+        let (merged, points) = self.rules.merge_line(&values);
+        let mut merged: Vec<Cell> = merged.into_iter().map(Cell::Tile).collect();
+        merged.resize(segment.len(), Cell::Empty);
+        (merged, points)
+    }
+
+    /// Indices of the cells of a row or column, beginning with
+    /// the cell all tiles will be moved towards.
+    fn line_indices(&self, direction: Direction, line: usize) -> Vec<usize> {
+        let size = self.size;
+        (0..size)
+            .map(|i| match direction {
+                Direction::Left => line * size + i,
+                Direction::Right => line * size + (size - 1 - i),
+                Direction::Up => i * size + line,
+                Direction::Down => (size - 1 - i) * size + line,
+            })
+            .collect()
+    }
+
+    /// Puts one of the smallest tiles (or the next one of the puzzle)
+    /// into a random empty cell. Returns the index and value of the
+    /// new tile.
+    pub fn spawn_tile(&mut self) -> Option<(usize, u32)> {
+        let index = self.random_empty_cell()?;
+        let value = match &mut self.spawns {
+            Some(spawns) => spawns.pop_front()?,
+            None => self.rules.spawn_value(&mut self.rng),
+        };
+        self.place_tile(SpawnedTile {
+            x: index % self.size,
+            y: index / self.size,
+            value,
+        });
+        Some((index, value))
+    }
+
+    fn random_empty_cell(&mut self) -> Option<usize> {
+        let empty: Vec<usize> = (0..self.cells.len())
+            .filter(|i| self.cells[*i] == Cell::Empty)
+            .collect();
+        if empty.is_empty() {
+            return None;
+        }
+        Some(empty[self.rng.gen_range(0, empty.len())])
+    }
+
+    /// Puts the tile onto the board as if it got spawned.
+    /// Meant for replaying recorded games.
+    pub fn place_tile(&mut self, tile: SpawnedTile) {
+        self.cells[tile.y * self.size + tile.x] = Cell::Tile(tile.value);
+        match self.history.last_mut() {
+            Some(last_move) if last_move.spawned.is_none() => last_move.spawned = Some(tile),
+            _ => self.start_tiles.push(tile),
+        }
+    }
+
+    /// Takes one of the smallest tiles off the board once no move
+    /// is possible in a zen game, so it never ends. Returns the index
+    /// of the removed tile.
+    pub fn make_room(&mut self) -> Option<usize> {
+        if self.mode != Mode::Zen || self.can_move() {
+            return None;
+        }
+        let (index, value) = self
+            .cells
+            .iter()
+            .enumerate()
+            .filter_map(|(i, cell)| Some((i, cell.tile()?)))
+            .min_by_key(|(_, value)| *value)?;
+        self.remove_tile(SpawnedTile {
+            x: index % self.size,
+            y: index / self.size,
+            value,
+        });
+        Some(index)
+    }
+
+    /// Takes the tile off the board as if it made room.
+    /// Meant for replaying recorded games.
+    pub fn remove_tile(&mut self, tile: SpawnedTile) {
+        self.cells[tile.y * self.size + tile.x] = Cell::Empty;
+        match self.history.last_mut() {
+            Some(last_move) => last_move.removed = Some(tile),
+            None => self.start_tiles.retain(|start_tile| *start_tile != tile),
+        }
+    }
+
+    /// Whether moving into some direction would change the board.
+    /// Empty cells that walls keep all tiles away from don't count.
+    pub fn can_move(&self) -> bool {
+        let directions = [
+            Direction::Up,
+            Direction::Down,
+            Direction::Left,
+            Direction::Right,
+        ];
+        directions.iter().any(|direction| {
+            (0..self.size).any(|line| {
+                self.segments(*direction, line).iter().any(|segment| {
+                    let (merged, _) = self.merge_segment(segment);
+                    segment
+                        .iter()
+                        .zip(merged)
+                        .any(|(i, cell)| self.cells[*i] != cell)
+                })
+            })
+        })
+    }
+
+    /// Whether there is something to continue: the game isn't over
+    /// and has been played or started from a given position.
+    pub fn in_progress(&self) -> bool {
+        (self.moves > 0 || self.start_tiles.len() > 1) && self.can_move()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Marks a wall in the cells passed to cells().
+    const X: u32 = u32::MAX;
+
+    /// Cells from tile values (row by row) with 0 being empty.
+    fn cells(values: &[u32]) -> Vec<Cell> {
+        values
+            .iter()
+            .map(|value| match *value {
+                0 => Cell::Empty,
+                X => Cell::Wall,
+                value => Cell::Tile(value),
+            })
+            .collect()
+    }
+
+    /// A classic game with the given cells and nothing spawned.
+    fn game(values: &[u32]) -> Game {
+        let mut game = Game::new(Mode::Classic, Rules::Powers { base: 2 }, 4, 0, 0);
+        game.cells = cells(values);
+        game
+    }
+
+    #[test]
+    fn merges_equal_tiles() {
+        #[rustfmt::skip]
+        let mut game = game(&[
+            2, 2, 4, 4,
+            2, 2, 2, 0,
+            4, 0, 4, 8,
+            2, 4, 8, 16,
+        ]);
+        assert!(game.play(Direction::Left));
+        #[rustfmt::skip]
+        assert_eq!(game.cells(), cells(&[
+            4, 8, 0, 0,
+            4, 2, 0, 0,
+            8, 8, 0, 0,
+            2, 4, 8, 16,
+        ]));
+        assert_eq!(game.score, 4 + 8 + 4 + 8);
+        assert_eq!(game.moves, 1);
+    }
+
+    #[test]
+    fn moves_towards_the_direction() {
+        #[rustfmt::skip]
+        let mut game = game(&[
+            2, 0, 0, 0,
+            2, 0, 0, 4,
+            0, 0, 0, 4,
+            4, 0, 0, 8,
+        ]);
+        assert!(game.play(Direction::Down));
+        #[rustfmt::skip]
+        assert_eq!(game.cells(), cells(&[
+            0, 0, 0, 0,
+            0, 0, 0, 0,
+            4, 0, 0, 8,
+            4, 0, 0, 8,
+        ]));
+        assert!(game.play(Direction::Right));
+        #[rustfmt::skip]
+        assert_eq!(game.cells(), cells(&[
+            0, 0, 0, 0,
+            0, 0, 0, 0,
+            0, 0, 4, 8,
+            0, 0, 4, 8,
+        ]));
+        assert!(game.play(Direction::Up));
+        assert_eq!(game.get(2, 0), Cell::Tile(8));
+        assert_eq!(game.get(3, 0), Cell::Tile(16));
+        assert_eq!(game.score, 4 + 8 + 8 + 16);
+    }
+
+    #[test]
+    fn merges_groups_in_base_three() {
+        let mut game = Game::new(Mode::PowersOfThree, Rules::Powers { base: 3 }, 4, 0, 0);
+        #[rustfmt::skip]
+        let values = [
+            3, 3, 3, 3,
+            3, 3, 0, 9,
+            9, 9, 9, 0,
+            0, 0, 0, 0,
+        ];
+        game.cells = cells(&values);
+        assert!(game.play(Direction::Right));
+        #[rustfmt::skip]
+        assert_eq!(game.cells(), cells(&[
+            0, 0, 3, 9,
+            0, 3, 3, 9,
+            0, 0, 0, 27,
+            0, 0, 0, 0,
+        ]));
+        assert_eq!(game.score, 9 + 27);
+    }
+
+    #[test]
+    fn blocked_moves_dont_count() {
+        let mut game = game(&[2, 4, 0, 0, 4, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert!(!game.play(Direction::Left));
+        assert!(!game.play(Direction::Up));
+        assert_eq!(game.moves, 0);
+        assert_eq!(game.score, 0);
+    }
+
+    #[test]
+    fn can_move_while_tiles_could_merge() {
+        #[rustfmt::skip]
+        let full = [
+            2, 4, 2, 4,
+            4, 2, 4, 2,
+            2, 4, 2, 4,
+            4, 2, 4, 2,
+        ];
+        assert!(!game(&full).can_move());
+        let mut values = full;
+        values[15] = 4;
+        assert!(game(&values).can_move());
+        values[15] = 0;
+        assert!(game(&values).can_move());
+    }
+
+    #[test]
+    fn tiles_stop_at_walls() {
+        #[rustfmt::skip]
+        let mut game = game(&[
+            2, X, 2, 2,
+            0, 2, X, 2,
+            4, 4, 0, X,
+            X, 0, 0, 8,
+        ]);
+        assert!(game.play(Direction::Left));
+        #[rustfmt::skip]
+        assert_eq!(game.cells(), cells(&[
+            2, X, 4, 0,
+            2, 0, X, 2,
+            8, 0, 0, X,
+            X, 8, 0, 0,
+        ]));
+        assert_eq!(game.score, 4 + 8);
+        assert!(game.play(Direction::Up));
+        #[rustfmt::skip]
+        assert_eq!(game.cells(), cells(&[
+            4, X, 4, 2,
+            8, 8, X, 0,
+            0, 0, 0, X,
+            X, 0, 0, 0,
+        ]));
+        assert_eq!(game.walls(), vec![(1, 0), (2, 1), (3, 2), (0, 3)]);
+    }
+
+    #[test]
+    fn walls_keep_tiles_from_merging() {
+        #[rustfmt::skip]
+        let mut game = game(&[
+            2, X, 2, 4,
+            4, 2, 4, 2,
+            2, 4, 2, 4,
+            4, 2, 4, 2,
+        ]);
+        assert!(!game.can_move());
+        for direction in [
+            Direction::Left,
+            Direction::Right,
+            Direction::Up,
+            Direction::Down,
+        ] {
+            assert!(!game.play(direction));
+        }
+    }
+
+    #[test]
+    fn places_walls_at_the_start() {
+        let game = Game::new(Mode::Obstacles, Rules::Powers { base: 2 }, 4, 0, 3);
+        assert_eq!(game.walls().len(), 3);
+        let tiles = game.cells().iter().filter(|cell| cell.tile().is_some());
+        assert_eq!(tiles.count(), 1);
+    }
+
+    #[test]
+    fn records_moves_and_spawns() {
+        let mut game = Game::new(Mode::Classic, Rules::Powers { base: 2 }, 4, 0, 0);
+        assert_eq!(game.start_tiles.len(), 1);
+        game.cells = cells(&[2, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert!(game.play(Direction::Left));
+        assert!(!game.play(Direction::Left));
+        let (index, value) = game.spawn_tile().unwrap();
+        assert_eq!(
+            game.history,
+            vec![MoveRecord {
+                direction: Direction::Left,
+                score_delta: 4,
+                spawned: Some(SpawnedTile {
+                    x: index % 4,
+                    y: index / 4,
+                    value,
+                }),
+                removed: None,
+            }]
+        );
+        assert_eq!(game.start_tiles.len(), 1);
+    }
+
+    #[test]
+    fn spawns_into_empty_cells() {
+        let mut game = game(&[2; 16]);
+        assert_eq!(game.spawn_tile(), None);
+        game.cells[5] = Cell::Empty;
+        let (index, value) = game.spawn_tile().unwrap();
+        assert_eq!(index, 5);
+        assert!(value == 2 || value == 4);
+    }
+
+    #[test]
+    fn zen_games_remove_the_smallest_tile_when_stuck() {
+        #[rustfmt::skip]
+        let stuck = cells(&[
+            2, 4, 2, 4,
+            4, 8, 4, 2,
+            2, 4, 16, 4,
+            4, 2, 4, 2,
+        ]);
+        let mut game = Game::with_cells(Mode::Zen, Mode::Zen.rules(2), 4, 0, stuck.clone(), 0);
+        assert_eq!(game.make_room(), Some(0));
+        assert_eq!(game.get(0, 0), Cell::Empty);
+        assert_eq!(game.start_tiles.len(), stuck.len() - 1);
+        assert!(game.can_move());
+        assert_eq!(game.make_room(), None);
+    }
+
+    #[test]
+    fn other_modes_dont_make_room() {
+        #[rustfmt::skip]
+        let mut game = game(&[
+            2, 4, 2, 4,
+            4, 8, 4, 2,
+            2, 4, 16, 4,
+            4, 2, 4, 2,
+        ]);
+        assert!(!game.can_move());
+        assert_eq!(game.make_room(), None);
+        assert_eq!(game.get(0, 0), Cell::Tile(2));
+    }
+
+    #[test]
+    fn records_removed_tiles_with_the_move() {
+        #[rustfmt::skip]
+        let mut game = Game::with_cells(Mode::Zen, Mode::Zen.rules(2), 4, 0, cells(&[
+            2, 2, 16, 8,
+            8, 32, 16, 2,
+            16, 4, 2, 4,
+            4, 2, 4, 8,
+        ]), 0);
+        assert!(game.play(Direction::Left));
+        game.cells[3] = Cell::Tile(32);
+        assert_eq!(game.make_room(), Some(7));
+        assert_eq!(
+            game.history.last().unwrap().removed,
+            Some(SpawnedTile {
+                x: 3,
+                y: 1,
+                value: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn time_attack_games_end_with_the_limit() {
+        let mut game = Game::new(Mode::TimeAttack, Mode::TimeAttack.rules(2), 4, 0, 0);
+        assert_eq!(game.time_left(), None);
+        game.time_limit = Some(Duration::from_secs(60));
+        assert_eq!(game.time_left(), Some(Duration::from_secs(60)));
+        assert!(!game.is_time_up());
+        game.clock.resume();
+        std::thread::sleep(Duration::from_millis(10));
+        game.clock.update();
+        assert!(game.time_left().unwrap() < Duration::from_secs(60));
+        game.time_limit = Some(Duration::from_millis(1));
+        assert!(game.is_time_up());
+        assert_eq!(game.duration(), Duration::from_millis(1));
+    }
+
+    #[test]
+    fn walled_off_cells_dont_count_as_room() {
+        #[rustfmt::skip]
+        let mut game = game(&[
+            0, X, 2, 4,
+            X, 2, 4, 2,
+            2, 4, 2, 4,
+            4, 2, 4, 2,
+        ]);
+        assert!(!game.can_move());
+        game.cells[2] = Cell::Empty;
+        assert!(game.can_move());
+    }
+}
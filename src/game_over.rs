@@ -0,0 +1,128 @@
+use crate::canvas::{mxcfb_rect, Canvas};
+use crate::game::Game;
+use crate::game_screen::{self, GameScreen};
+use crate::highscore_screen::{self, NameEntry};
+use crate::highscores::{self, HighScore, HighScores};
+use crate::screen::{Context, Input, Screen, Transition};
+use crate::stats::{self, GameRecord, Stats};
+use crate::stats_screen;
+use anyhow::Result;
+
+/// What gets shown after a game.
+enum Summary {
+    /// Stats of all games including the one that just ended
+    Stats { stats: Stats, heading: String },
+    /// The table the game made it into
+    HighScores {
+        title: String,
+        table: Vec<HighScore>,
+        rank: Option<usize>,
+    },
+}
+
+/// Stats or high scores with a "New game" button.
+pub struct GameOverScreen {
+    summary: Summary,
+    new_game_button: mxcfb_rect,
+}
+
+/// Records the finished game. Asks for initials if it made it
+/// into the high scores and shows the stats otherwise.
+pub fn screen(game: &Game) -> Box<dyn Screen> {
+    let record = GameRecord::from_game(game);
+    if let Err(e) = stats::record_game(&record) {
+        error!("Failed to record game: {:?}", e);
+    }
+
+    let is_highscore = HighScores::load()
+        .map(|highscores| highscores.qualifies(&record))
+        .unwrap_or_else(|e| {
+            error!("Failed to load high scores: {:?}", e);
+            false
+        });
+    if is_highscore {
+        return Box::new(NameEntry::new(record));
+    }
+
+    let records = stats::load_games().unwrap_or_else(|e| {
+        error!("Failed to load recorded games: {:?}", e);
+        vec![record]
+    });
+    // Time attack games can end with moves left
+    let ending = if game.can_move() {
+        "Time's up!"
+    } else {
+        "Game over!"
+    };
+    Box::new(GameOverScreen::new(Summary::Stats {
+        stats: Stats::from_records(&records),
+        heading: format!("{} Score: {}", ending, game.score),
+    }))
+}
+
+impl GameOverScreen {
+    fn new(summary: Summary) -> Self {
+        Self {
+            summary,
+            new_game_button: mxcfb_rect::invalid(),
+        }
+    }
+
+    /// Saves the high score and shows its table. Nothing is saved if
+    /// the high scores can't be loaded, as that would lose them.
+    pub fn new_high_score(record: &GameRecord, name: &str) -> Self {
+        let mut highscores = match HighScores::load() {
+            Ok(highscores) => highscores,
+            Err(e) => {
+                error!("Not saving the high score: {:?}", e);
+                return Self::new(Summary::HighScores {
+                    title: "Failed to load, not saved".to_owned(),
+                    table: Vec::new(),
+                    rank: None,
+                });
+            }
+        };
+        let rank = highscores.insert(record, name);
+        info!(
+            "{} made rank {:?} with a score of {}",
+            name, rank, record.score
+        );
+        let title = match highscores.save() {
+            Ok(()) => highscores::table_name(record),
+            Err(e) => {
+                error!("Failed to save high scores: {:?}", e);
+                "Failed to save".to_owned()
+            }
+        };
+        Self::new(Summary::HighScores {
+            title,
+            table: highscores.table(record).to_vec(),
+            rank,
+        })
+    }
+}
+
+impl Screen for GameOverScreen {
+    fn draw(&mut self, ctx: &mut Context) -> Result<()> {
+        let canvas = &mut ctx.canvas;
+        self.new_game_button = match &self.summary {
+            Summary::Stats { stats, heading } => {
+                stats_screen::draw(canvas, stats, Some(heading), "New game")
+            }
+            Summary::HighScores { title, table, rank } => {
+                highscore_screen::draw_table(canvas, title, table, *rank)
+            }
+        };
+        Ok(())
+    }
+
+    fn handle_input(&mut self, ctx: &mut Context, input: Input) -> Result<Transition> {
+        Ok(match input {
+            Input::Tap(pos) if Canvas::is_hitting(pos, self.new_game_button) => {
+                Transition::Reset(Box::new(GameScreen::new_game(&ctx.config)))
+            }
+            Input::Action(action) => game_screen::handle_action(ctx, action),
+            _ => Transition::Stay,
+        })
+    }
+}
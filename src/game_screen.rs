@@ -0,0 +1,309 @@
+use crate::board_code::Board;
+use crate::canvas::{Canvas, Orientation};
+use crate::config::{Action, Config};
+use crate::game::{Direction, Game};
+use crate::game_over;
+use crate::menu::Menu;
+use crate::puzzles::{Outcome, Pack, Puzzle, PuzzleProgress};
+use crate::screen::{Context, Input, Screen, Transition};
+use crate::screenshot;
+use crate::settings_screen::SettingsScreen;
+use crate::swipe::Direction as SwipeDirection;
+use crate::timer;
+use anyhow::Result;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Time to look at the final board before the stats are shown.
+pub const GAME_OVER_DELAY: Duration = Duration::from_millis(1500);
+
+/// The board of the game being played.
+pub struct GameScreen {
+    game: Game,
+    /// Pack and index of the puzzle being solved
+    puzzle: Option<(Pack, usize)>,
+}
+
+impl GameScreen {
+    pub fn new(game: Game) -> Self {
+        Self { game, puzzle: None }
+    }
+
+    /// The puzzle with the given index in the pack.
+    pub fn puzzle(pack: Pack, index: usize) -> Result<Self> {
+        let puzzle = pack
+            .puzzles
+            .get(index)
+            .ok_or_else(|| anyhow!("There is no puzzle {} in {:?}", index, pack.name))?;
+        info!("Starting puzzle {:?}", puzzle.name);
+        Ok(Self {
+            game: puzzle.game()?,
+            puzzle: Some((pack, index)),
+        })
+    }
+
+    fn current_puzzle(&self) -> Option<&Puzzle> {
+        self.puzzle
+            .as_ref()
+            .map(|(pack, index)| &pack.puzzles[*index])
+    }
+
+    /// Whether the game ended while moves would still be possible.
+    fn ended_early(&self) -> bool {
+        self.game.is_time_up()
+            || self
+                .current_puzzle()
+                .is_some_and(|puzzle| puzzle.outcome(&self.game).is_some())
+    }
+
+    /// A game with a random seed in the configured mode and size.
+    pub fn new_game(config: &Config) -> Self {
+        let seed = rand::random();
+        info!("Starting new game with seed {}", seed);
+        let mut game = Game::new(
+            config.mode,
+            config.rules(),
+            config.board_size,
+            seed,
+            config.start_walls(),
+        );
+        game.time_limit = config.time_limit();
+        Self::new(game)
+    }
+
+    /// Moves the tiles and spawns a new one.
+    fn play(&mut self, ctx: &mut Context, direction: Direction) -> Result<Transition> {
+        self.game.clock.update();
+        if self.game.is_time_up() {
+            return Ok(self.time_up());
+        }
+        let (canvas, view, game) = (&mut ctx.canvas, &mut ctx.view, &mut self.game);
+        let start = Instant::now();
+        let last = game.cells().to_vec();
+        let moved = game.play(direction);
+        let markers = view.draw_cells(canvas, Some(&last), game.cells())?;
+        if moved {
+            view.draw_score(canvas, game);
+            // Let the player see the move before the new tile appears
+            canvas.wait_for_updates(&markers);
+            thread::sleep(ctx.config.spawn_delay().saturating_sub(start.elapsed()));
+            let last = game.cells().to_vec();
+            if game.spawn_tile().is_some() {
+                let markers = view.animate_spawned_tiles(canvas, &last, game.cells())?;
+                canvas.wait_for_updates(&markers);
+            }
+        }
+        // Zen games go on with one of the smallest tiles less
+        let last = game.cells().to_vec();
+        if game.make_room().is_some() {
+            thread::sleep(ctx.config.spawn_delay());
+            let markers = view.draw_cells(canvas, Some(&last), game.cells())?;
+            canvas.wait_for_updates(&markers);
+        }
+        debug!("Move {:?} took {:?}", direction, start.elapsed());
+
+        if let Some(puzzle) = self.current_puzzle() {
+            if moved {
+                ctx.view
+                    .draw_status(&mut ctx.canvas, &puzzle.status(&self.game));
+            }
+            return Ok(match puzzle.outcome(&self.game) {
+                Some(outcome) => self.puzzle_over(outcome),
+                None => Transition::Stay,
+            });
+        }
+        // The move took a while to draw
+        self.game.clock.update();
+        let game = &self.game;
+        if game.can_move() {
+            return Ok(Transition::Stay);
+        }
+        info!("Game over with a score of {}", game.score);
+        log_board_code(game);
+        thread::sleep(GAME_OVER_DELAY);
+        Ok(Transition::Replace(game_over::screen(game)))
+    }
+
+    /// Counts the time played and shows the time left in time
+    /// attack games.
+    fn tick(&mut self, ctx: &mut Context) -> Transition {
+        self.game.clock.update();
+        let left = match self.game.time_left() {
+            Some(left) => left,
+            None => return Transition::Stay,
+        };
+        ctx.view.draw_status(&mut ctx.canvas, &timer::format(left));
+        if left.is_zero() {
+            return self.time_up();
+        }
+        Transition::Stay
+    }
+
+    fn time_up(&mut self) -> Transition {
+        info!("Time is up with a score of {}", self.game.score);
+        log_board_code(&self.game);
+        thread::sleep(GAME_OVER_DELAY);
+        Transition::Replace(game_over::screen(&self.game))
+    }
+
+    /// Records the attempt and offers to try again or go on.
+    fn puzzle_over(&self, outcome: Outcome) -> Transition {
+        let (pack, index) = match &self.puzzle {
+            Some(puzzle) => puzzle,
+            None => return Transition::Stay,
+        };
+        let puzzle = &pack.puzzles[*index];
+        let moves = self.game.moves;
+        info!(
+            "Puzzle {:?} ended after {} moves: {:?}",
+            puzzle.name, moves, outcome
+        );
+        // Saving after a failed load would lose the progress so far
+        let saved = PuzzleProgress::load().and_then(|mut progress| {
+            progress.record(
+                pack.key(puzzle),
+                (outcome == Outcome::Solved).then_some(moves),
+            );
+            progress.save()
+        });
+        let error = saved.err().map(|e| {
+            error!("Not saving the puzzle progress: {:?}", e);
+            "The progress couldn't be saved".to_owned()
+        });
+
+        let title = match outcome {
+            Outcome::Solved if moves == 1 => "Solved in 1 move!".to_owned(),
+            Outcome::Solved => format!("Solved in {} moves!", moves),
+            Outcome::OutOfMoves => "Out of moves".to_owned(),
+            Outcome::Stuck => "No moves left".to_owned(),
+        };
+        thread::sleep(GAME_OVER_DELAY);
+        let next = Some(index + 1).filter(|next| *next < pack.puzzles.len());
+        Transition::Replace(Box::new(Menu::puzzle_over(title, *index, next, error)))
+    }
+
+    /// Shows the screen on top. The clock waits meanwhile.
+    fn push(&mut self, screen: Box<dyn Screen>) -> Transition {
+        self.game.clock.pause();
+        Transition::Push(screen)
+    }
+
+    fn take_screenshot(&self, ctx: &mut Context) {
+        match screenshot::save(&mut ctx.canvas, ctx.view.layout.screenshot_area()) {
+            Ok(path) => {
+                info!("Saved screenshot to {:?}", path);
+                log_board_code(&self.game);
+            }
+            Err(e) => error!("Failed to take screenshot: {:?}", e),
+        }
+    }
+}
+
+impl Screen for GameScreen {
+    /// Applies changes of the config that happened meanwhile.
+    fn on_enter(&mut self, ctx: &mut Context) -> Result<()> {
+        let changed = ctx
+            .view
+            .reconfigure(&ctx.config, self.game.size, self.game.rules);
+        self.draw(ctx)?;
+        if changed {
+            ctx.view.prerender(&mut ctx.canvas);
+        }
+        // Only runs once the board can be seen
+        self.game.clock.resume();
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> Result<()> {
+        ctx.view.draw_game(&mut ctx.canvas, &self.game)?;
+        if let Some(left) = self.game.time_left() {
+            ctx.view.draw_status(&mut ctx.canvas, &timer::format(left));
+        }
+        if let Some(puzzle) = self.current_puzzle() {
+            ctx.view
+                .draw_status(&mut ctx.canvas, &puzzle.status(&self.game));
+        }
+        Ok(())
+    }
+
+    fn handle_input(&mut self, ctx: &mut Context, input: Input) -> Result<Transition> {
+        let layout = ctx.view.layout;
+        Ok(match input {
+            Input::Swipe(swipe, _) => {
+                let direction = swipe_direction(ctx.canvas.orientation(), swipe);
+                return self.play(ctx, direction);
+            }
+            Input::Tap(pos) if Canvas::is_hitting(pos, layout.settings_button) => {
+                self.push(Box::new(SettingsScreen::new(ctx.config)))
+            }
+            Input::Tap(pos) if Canvas::is_hitting(pos, layout.menu_button) => {
+                self.push(Box::new(Menu::main(self.game.in_progress())))
+            }
+            // Tapping the title takes a screenshot
+            Input::Tap(pos) if Canvas::is_hitting(pos, layout.header) => {
+                self.take_screenshot(ctx);
+                Transition::Stay
+            }
+            Input::Action(Action::Screenshot) => {
+                self.take_screenshot(ctx);
+                Transition::Stay
+            }
+            Input::Action(Action::Menu) => self.push(Box::new(Menu::main(self.game.in_progress()))),
+            Input::Action(action) => handle_action(ctx, action),
+            Input::Config(_) => {
+                ctx.canvas.set_orientation(ctx.config.orientation);
+                if ctx
+                    .view
+                    .reconfigure(&ctx.config, self.game.size, self.game.rules)
+                {
+                    self.draw(ctx)?;
+                    ctx.view.prerender(&mut ctx.canvas);
+                }
+                Transition::Stay
+            }
+            Input::Tick => self.tick(ctx),
+            _ => Transition::Stay,
+        })
+    }
+
+    fn on_exit(&mut self, _ctx: &mut Context) {
+        if self.game.in_progress() && !self.ended_early() {
+            info!("Abandoning game with a score of {}", self.game.score);
+        }
+    }
+}
+
+/// What the keymap does on screens that don't handle the actions
+/// themselves. Games in progress are left behind.
+pub fn handle_action(ctx: &Context, action: Action) -> Transition {
+    match action {
+        Action::None | Action::Screenshot => Transition::Stay,
+        Action::Menu => Transition::Reset(Box::new(Menu::main(false))),
+        Action::NewGame => Transition::Reset(Box::new(GameScreen::new_game(&ctx.config))),
+        Action::Quit => Transition::Quit,
+    }
+}
+
+/// Direction of the move as seen by the player. Swipes are
+/// detected on the touchscreen, which doesn't rotate.
+pub fn swipe_direction(orientation: Orientation, swipe: SwipeDirection) -> Direction {
+    match (orientation, swipe) {
+        (Orientation::Portrait, SwipeDirection::Up) => Direction::Up,
+        (Orientation::Portrait, SwipeDirection::Right) => Direction::Right,
+        (Orientation::Portrait, SwipeDirection::Down) => Direction::Down,
+        (Orientation::Portrait, SwipeDirection::Left) => Direction::Left,
+        (Orientation::Landscape, SwipeDirection::Up) => Direction::Left,
+        (Orientation::Landscape, SwipeDirection::Right) => Direction::Up,
+        (Orientation::Landscape, SwipeDirection::Down) => Direction::Right,
+        (Orientation::Landscape, SwipeDirection::Left) => Direction::Down,
+    }
+}
+
+/// Logs the code that can be passed to --board to
+/// continue from the current position.
+fn log_board_code(game: &Game) {
+    match Board::from_game(game).and_then(|board| board.encode()) {
+        Ok(code) => info!("Board code: {}", code),
+        Err(e) => warn!("Board can't be shared: {:?}", e),
+    }
+}
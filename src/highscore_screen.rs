@@ -0,0 +1,235 @@
+use crate::canvas::{color, mxcfb_rect, Canvas, Point2, Refresh, Vector2};
+use crate::game_over::GameOverScreen;
+use crate::game_screen;
+use crate::highscores::{HighScore, MAX_NAME_LENGTH};
+use crate::screen::{Context, Input, Screen, Transition};
+use crate::stats::GameRecord;
+use anyhow::Result;
+
+const TITLE_FONT_SIZE: f32 = 100.0;
+const HEADLINE_FONT_SIZE: f32 = 60.0;
+const NAME_FONT_SIZE: f32 = 140.0;
+/// Baseline of the entered initials
+const NAME_Y: i32 = 560;
+const KEY_FONT_SIZE: f32 = 70.0;
+const KEY_WIDTH: u32 = 150;
+const KEY_HEIGHT: u32 = 130;
+const KEY_SPACING: u32 = 30;
+const KEYS_PER_ROW: usize = 7;
+/// Top of the first row of the letter picker
+const KEYS_TOP: u32 = 700;
+const LINE_FONT_SIZE: f32 = 55.0;
+const LINE_HEIGHT: i32 = 95;
+const RANK_X: i32 = 150;
+const NAME_X: i32 = 300;
+const SCORE_X: i32 = 550;
+const TILE_X: i32 = 900;
+const BUTTON_GAP: u32 = 20;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Key {
+    Letter(char),
+    Delete,
+    Done,
+}
+
+/// On-screen letter picker to enter the initials for a new high score.
+pub struct NameEntry {
+    record: GameRecord,
+    name: String,
+    keys: Vec<(mxcfb_rect, Key)>,
+}
+
+impl NameEntry {
+    pub fn new(record: GameRecord) -> Self {
+        Self {
+            record,
+            name: String::new(),
+            keys: Vec::new(),
+        }
+    }
+
+    /// Handles a tap on the picker. Returns the initials once
+    /// they are confirmed.
+    fn handle_tap(&mut self, canvas: &mut Canvas, pos: Point2<u16>) -> Option<String> {
+        let key = self
+            .keys
+            .iter()
+            .find(|(hitbox, _)| Canvas::is_hitting(pos, *hitbox))
+            .map(|(_, key)| *key)?;
+        match key {
+            Key::Letter(letter) if self.name.len() < MAX_NAME_LENGTH => self.name.push(letter),
+            Key::Delete => {
+                self.name.pop();
+            }
+            Key::Done if !self.name.is_empty() => return Some(self.name.clone()),
+            _ => return None,
+        }
+        let area = self.draw_name(canvas);
+        canvas.update_partial(&area, Refresh::Normal);
+        None
+    }
+
+    /// Shows the initials with placeholders for missing letters.
+    fn draw_name(&self, canvas: &mut Canvas) -> mxcfb_rect {
+        let area = canvas.fill_rect(
+            Point2 {
+                x: Some(0),
+                y: Some(NAME_Y - NAME_FONT_SIZE as i32),
+            },
+            Vector2 {
+                x: canvas.width(),
+                y: NAME_FONT_SIZE as u32 + 30,
+            },
+            color::WHITE,
+        );
+        let mut letters: Vec<String> = self.name.chars().map(|letter| letter.to_string()).collect();
+        letters.resize(MAX_NAME_LENGTH, "_".to_owned());
+        canvas.draw_text(
+            Point2 {
+                x: None,
+                y: Some(NAME_Y),
+            },
+            &letters.join(" "),
+            NAME_FONT_SIZE,
+        );
+        area
+    }
+}
+
+impl Screen for NameEntry {
+    fn draw(&mut self, ctx: &mut Context) -> Result<()> {
+        let canvas = &mut ctx.canvas;
+        canvas.clear();
+        canvas.draw_text(
+            Point2 {
+                x: None,
+                y: Some(175),
+            },
+            "New high score!",
+            TITLE_FONT_SIZE,
+        );
+        canvas.draw_text(
+            Point2 {
+                x: None,
+                y: Some(300),
+            },
+            &format!("Score: {}. Enter your initials:", self.record.score),
+            HEADLINE_FONT_SIZE,
+        );
+
+        let labels = ('A'..='Z')
+            .map(|letter| (letter.to_string(), Key::Letter(letter)))
+            .chain(vec![
+                ("Del".to_owned(), Key::Delete),
+                ("OK".to_owned(), Key::Done),
+            ]);
+        let row_width = KEYS_PER_ROW as u32 * (KEY_WIDTH + KEY_SPACING) - KEY_SPACING;
+        let left = (canvas.width() - row_width) / 2;
+        self.keys.clear();
+        for (i, (label, key)) in labels.enumerate() {
+            let (column, row) = ((i % KEYS_PER_ROW) as u32, (i / KEYS_PER_ROW) as u32);
+            let area = mxcfb_rect {
+                left: left + column * (KEY_WIDTH + KEY_SPACING),
+                top: KEYS_TOP + row * (KEY_HEIGHT + KEY_SPACING),
+                width: KEY_WIDTH,
+                height: KEY_HEIGHT,
+            };
+            self.keys
+                .push((canvas.draw_fixed_button(area, &label, KEY_FONT_SIZE), key));
+        }
+
+        self.draw_name(canvas);
+        canvas.update_full(Refresh::Quality);
+        Ok(())
+    }
+
+    fn handle_input(&mut self, ctx: &mut Context, input: Input) -> Result<Transition> {
+        Ok(match input {
+            Input::Tap(pos) => match self.handle_tap(&mut ctx.canvas, pos) {
+                Some(name) => Transition::Replace(Box::new(GameOverScreen::new_high_score(
+                    &self.record,
+                    &name,
+                ))),
+                None => Transition::Stay,
+            },
+            Input::Action(action) => game_screen::handle_action(ctx, action),
+            _ => Transition::Stay,
+        })
+    }
+}
+
+/// Draws a high score table onto a cleared screen. The entry at
+/// `highlight` (e.g. the one just added) gets a frame around it.
+/// Returns the hitbox of the "New game" button.
+pub fn draw_table(
+    canvas: &mut Canvas,
+    title: &str,
+    table: &[HighScore],
+    highlight: Option<usize>,
+) -> mxcfb_rect {
+    canvas.clear();
+    canvas.draw_text(
+        Point2 {
+            x: None,
+            y: Some(175),
+        },
+        "High scores",
+        TITLE_FONT_SIZE,
+    );
+    canvas.draw_text(
+        Point2 {
+            x: None,
+            y: Some(300),
+        },
+        title,
+        HEADLINE_FONT_SIZE,
+    );
+
+    let mut y = 480;
+    for (rank, entry) in table.iter().enumerate() {
+        let columns = [
+            (RANK_X, format!("{}.", rank + 1)),
+            (NAME_X, entry.name.clone()),
+            (SCORE_X, entry.score.to_string()),
+            (TILE_X, format!("({})", entry.max_tile)),
+        ];
+        for (x, text) in columns.iter() {
+            canvas.draw_text(
+                Point2 {
+                    x: Some(*x),
+                    y: Some(y),
+                },
+                text,
+                LINE_FONT_SIZE,
+            );
+        }
+        if highlight == Some(rank) {
+            canvas.draw_rect(
+                Point2 {
+                    x: Some(RANK_X - 30),
+                    y: Some(y - LINE_HEIGHT + 20),
+                },
+                Vector2 {
+                    x: canvas.width() - 2 * (RANK_X as u32 - 30),
+                    y: LINE_HEIGHT as u32,
+                },
+                3,
+            );
+        }
+        y += LINE_HEIGHT;
+    }
+
+    let button = canvas.draw_button(
+        Point2 {
+            x: None,
+            y: Some(canvas.height() as i32 - 80),
+        },
+        "New game",
+        LINE_FONT_SIZE,
+        BUTTON_GAP,
+        BUTTON_GAP,
+    );
+    canvas.update_full(Refresh::Quality);
+    button
+}
@@ -0,0 +1,200 @@
+use crate::stats::{self, GameRecord};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+
+const HIGHSCORES_FILE: &str = "highscores.json";
+/// Entries kept per mode and board size.
+pub const HIGHSCORE_TABLE_SIZE: usize = 10;
+/// Maximum length of the initials.
+pub const MAX_NAME_LENGTH: usize = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighScore {
+    pub name: String,
+    pub score: u32,
+    pub max_tile: u32,
+    /// Unix timestamp
+    pub finished_at: u64,
+}
+
+/// Best scores per mode and board size, stored in HIGHSCORES_FILE.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HighScores {
+    /// See table_name() => Best first
+    tables: BTreeMap<String, Vec<HighScore>>,
+}
+
+/// Key and title of the table the game belongs to.
+pub fn table_name(record: &GameRecord) -> String {
+    format!(
+        "{} {}x{}",
+        record.mode_name(),
+        record.board_size,
+        record.board_size
+    )
+}
+
+impl HighScores {
+    pub fn load() -> Result<Self> {
+        let path = stats::data_dir().join(HIGHSCORES_FILE);
+        match fs::read_to_string(&path) {
+            Ok(json) => {
+                serde_json::from_str(&json).with_context(|| format!("Failed to parse {:?}", path))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {:?}", path)),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let dir = stats::data_dir();
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {:?}", dir))?;
+        let path = dir.join(HIGHSCORES_FILE);
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    /// All tables by their names (see table_name()).
+    pub fn tables(&self) -> impl Iterator<Item = (&String, &Vec<HighScore>)> {
+        self.tables.iter()
+    }
+
+    /// The table the game belongs to.
+    pub fn table(&self, record: &GameRecord) -> &[HighScore] {
+        self.tables
+            .get(&table_name(record))
+            .map(|table| table.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Whether the game would make it into its table.
+    pub fn qualifies(&self, record: &GameRecord) -> bool {
+        let table = self.table(record);
+        record.score > 0
+            && (table.len() < HIGHSCORE_TABLE_SIZE
+                || table.last().map(|worst| record.score > worst.score) == Some(true))
+    }
+
+    /// Adds the game to its table and returns its rank (starting at 0).
+    /// Ties are ranked below older entries.
+    pub fn insert(&mut self, record: &GameRecord, name: &str) -> Option<usize> {
+        let table = self.tables.entry(table_name(record)).or_default();
+        let rank = table
+            .iter()
+            .position(|entry| entry.score < record.score)
+            .unwrap_or(table.len());
+        if rank >= HIGHSCORE_TABLE_SIZE {
+            return None;
+        }
+        table.insert(
+            rank,
+            HighScore {
+                name: name.to_owned(),
+                score: record.score,
+                max_tile: record.max_tile,
+                finished_at: record.finished_at,
+            },
+        );
+        table.truncate(HIGHSCORE_TABLE_SIZE);
+        Some(rank)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Game, Mode};
+    use crate::rules::{Rules, DEFAULT_BASE};
+
+    fn record(mode: Mode, board_size: usize, score: u32) -> GameRecord {
+        let mut game = Game::new(mode, mode.rules(DEFAULT_BASE), board_size, 0, 0);
+        game.score = score;
+        GameRecord::from_game(&game)
+    }
+
+    fn scores(high_scores: &HighScores) -> Vec<u32> {
+        high_scores
+            .table(&record(Mode::Classic, 4, 0))
+            .iter()
+            .map(|entry| entry.score)
+            .collect()
+    }
+
+    #[test]
+    fn ranks_inserted_scores() {
+        let mut high_scores = HighScores::default();
+        assert_eq!(
+            high_scores.insert(&record(Mode::Classic, 4, 200), "AAA"),
+            Some(0)
+        );
+        assert_eq!(
+            high_scores.insert(&record(Mode::Classic, 4, 300), "BBB"),
+            Some(0)
+        );
+        assert_eq!(
+            high_scores.insert(&record(Mode::Classic, 4, 100), "CCC"),
+            Some(2)
+        );
+        // Ties go below the older entry
+        assert_eq!(
+            high_scores.insert(&record(Mode::Classic, 4, 200), "DDD"),
+            Some(2)
+        );
+        assert_eq!(scores(&high_scores), vec![300, 200, 200, 100]);
+        let names: Vec<&str> = high_scores
+            .table(&record(Mode::Classic, 4, 0))
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["BBB", "AAA", "DDD", "CCC"]);
+    }
+
+    #[test]
+    fn keeps_the_best_scores_only() {
+        let mut high_scores = HighScores::default();
+        for score in 1..=HIGHSCORE_TABLE_SIZE as u32 {
+            let record = record(Mode::Classic, 4, score * 10);
+            assert!(high_scores.qualifies(&record));
+            high_scores.insert(&record, "AAA");
+        }
+        assert!(!high_scores.qualifies(&record(Mode::Classic, 4, 10)));
+        assert_eq!(
+            high_scores.insert(&record(Mode::Classic, 4, 5), "BBB"),
+            None
+        );
+
+        assert!(high_scores.qualifies(&record(Mode::Classic, 4, 11)));
+        assert_eq!(
+            high_scores.insert(&record(Mode::Classic, 4, 11), "BBB"),
+            Some(9)
+        );
+        assert_eq!(scores(&high_scores).len(), HIGHSCORE_TABLE_SIZE);
+        assert_eq!(scores(&high_scores).last(), Some(&11));
+    }
+
+    #[test]
+    fn keeps_a_table_per_board_size() {
+        let mut high_scores = HighScores::default();
+        high_scores.insert(&record(Mode::Classic, 4, 100), "AAA");
+        assert!(high_scores.table(&record(Mode::Classic, 5, 0)).is_empty());
+        assert_eq!(scores(&high_scores), vec![100]);
+        assert!(!high_scores.qualifies(&record(Mode::Classic, 5, 0)));
+        assert!(high_scores.qualifies(&record(Mode::Classic, 5, 1)));
+    }
+
+    #[test]
+    fn keeps_a_table_per_base() {
+        let record = |base| {
+            let mut game = Game::new(Mode::CustomBase, Rules::Powers { base }, 4, 0, 0);
+            game.score = 100;
+            GameRecord::from_game(&game)
+        };
+        let mut high_scores = HighScores::default();
+        high_scores.insert(&record(3), "AAA");
+        assert_eq!(table_name(&record(3)), "Base3 4x4");
+        assert_eq!(high_scores.table(&record(3)).len(), 1);
+        assert!(high_scores.table(&record(5)).is_empty());
+    }
+}
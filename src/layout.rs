@@ -0,0 +1,382 @@
+use crate::canvas::{mxcfb_rect, Orientation, Point2};
+
+/// Width and height of the board including the margins of its cells.
+const BOARD_EXTENT: u32 = 1312;
+/// Space around each cell. Two cells are 2 * CELL_MARGIN apart.
+const CELL_MARGIN: u32 = 4;
+/// Space between the cells and the frame around the board
+const BOARD_OUTER_MARGIN: u32 = 4;
+const TITLE_BASELINE: i32 = 175;
+const TITLE_FONT_SIZE: f32 = 125.0;
+/// Fits next to the board in landscape.
+const LANDSCAPE_TITLE_FONT_SIZE: f32 = 90.0;
+pub const SCORE_FONT_SIZE: f32 = 70.0;
+/// Baseline of the score below the board (portrait)
+const SCORE_Y: u32 = 1700;
+/// Baseline of the score below the title (landscape)
+const LANDSCAPE_SCORE_Y: u32 = 400;
+/// Longer status lines get smaller to fit
+pub const STATUS_FONT_SIZE: f32 = 50.0;
+/// Baseline of the status line between the title and the board (portrait)
+const STATUS_Y: u32 = 258;
+/// Baseline of the status line below the menu button (landscape)
+const LANDSCAPE_STATUS_Y: u32 = 840;
+/// Width of the status line in portrait. It gets the whole
+/// width of the header in landscape.
+const STATUS_WIDTH: u32 = 900;
+const HEADER_BUTTON_WIDTH: u32 = 240;
+const HEADER_BUTTON_HEIGHT: u32 = 90;
+/// Distance of the header buttons to the corners of the screen (portrait)
+const HEADER_BUTTON_MARGIN: u32 = 40;
+/// Top of the settings button below the score (landscape)
+const LANDSCAPE_SETTINGS_BUTTON_Y: u32 = 520;
+/// Top of the menu button below the settings button (landscape)
+const LANDSCAPE_MENU_BUTTON_Y: u32 = 640;
+/// Width and height of each board of a versus game (portrait)
+const VERSUS_BOARD_EXTENT: u32 = 760;
+/// Fits into half the width of the screen (landscape)
+const LANDSCAPE_VERSUS_BOARD_EXTENT: u32 = 860;
+/// Top of the board of each player, from the top of their half
+const VERSUS_BOARD_Y: u32 = 120;
+const LANDSCAPE_VERSUS_BOARD_Y: u32 = 200;
+/// Baseline of the status line of each player above their board
+const VERSUS_STATUS_Y: u32 = 80;
+const LANDSCAPE_VERSUS_STATUS_Y: u32 = 130;
+/// Leaves room for the menu button next to the status line (portrait)
+const VERSUS_STATUS_WIDTH: u32 = 700;
+
+/// Which hand the player mostly uses. Buttons end up on that side.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, clap::ValueEnum)]
+pub enum Handedness {
+    Right,
+    /// Also moves the board to the right in landscape
+    Left,
+}
+
+/// Where the cells of a board are.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BoardLayout {
+    pub size: usize,
+    pub cell_size: u32,
+    /// All cells including their margins
+    pub area: mxcfb_rect,
+}
+
+impl BoardLayout {
+    /// Fits a board with `size` x `size` cells into the square
+    /// with the given top left corner and extent.
+    pub fn new(size: usize, top_left: Point2<u32>, extent: u32) -> Self {
+        let pitch = extent / size as u32;
+        // Centers the board should the extent not be divisible by the size
+        let offset = (extent - pitch * size as u32) / 2;
+        Self {
+            size,
+            cell_size: pitch - 2 * CELL_MARGIN,
+            area: mxcfb_rect {
+                left: top_left.x + offset,
+                top: top_left.y + offset,
+                width: pitch * size as u32,
+                height: pitch * size as u32,
+            },
+        }
+    }
+
+    /// Distance between the left (or top) sides of two neighboring cells.
+    pub fn cell_pitch(&self) -> u32 {
+        self.cell_size + 2 * CELL_MARGIN
+    }
+
+    /// Area of the cell with the given index (row by row).
+    pub fn cell_area(&self, index: usize) -> mxcfb_rect {
+        let (x, y) = ((index % self.size) as u32, (index / self.size) as u32);
+        mxcfb_rect {
+            left: self.area.left + x * self.cell_pitch(),
+            top: self.area.top + y * self.cell_pitch(),
+            width: self.cell_size,
+            height: self.cell_size,
+        }
+    }
+
+    /// The board including the frame around it. The margins of
+    /// the last row and column already make up for the frame there.
+    pub fn frame_area(&self) -> mxcfb_rect {
+        mxcfb_rect {
+            left: self.area.left - BOARD_OUTER_MARGIN,
+            top: self.area.top - BOARD_OUTER_MARGIN,
+            width: self.area.width + BOARD_OUTER_MARGIN,
+            height: self.area.height + BOARD_OUTER_MARGIN,
+        }
+    }
+}
+
+/// Positions of everything on the game screen.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Layout {
+    pub orientation: Orientation,
+    pub handedness: Handedness,
+    pub board: BoardLayout,
+    /// Everything above (portrait) or next to (landscape) the board
+    pub header: mxcfb_rect,
+    /// Horizontal center and baseline of the title
+    pub title_pos: Point2<i32>,
+    pub title_font_size: f32,
+    /// Strip the score gets drawn into
+    pub score_area: mxcfb_rect,
+    /// Horizontal center and baseline of the score
+    pub score_pos: Point2<i32>,
+    /// Strip for the timer of time attack games or the goal of puzzles
+    pub status_area: mxcfb_rect,
+    /// Horizontal center and baseline of the status line
+    pub status_pos: Point2<i32>,
+    /// Opens the settings. Part of the header.
+    pub settings_button: mxcfb_rect,
+    /// Shows the start screen. Part of the header.
+    pub menu_button: mxcfb_rect,
+}
+
+impl Layout {
+    pub fn new(orientation: Orientation, handedness: Handedness, board_size: usize) -> Self {
+        let screen = orientation.size();
+        match orientation {
+            Orientation::Portrait => {
+                let board = BoardLayout::new(
+                    board_size,
+                    Point2 {
+                        x: (screen.x - BOARD_EXTENT) / 2,
+                        y: (screen.y - BOARD_EXTENT) / 2,
+                    },
+                    BOARD_EXTENT,
+                );
+                // Settings on the side of the dominant hand, the menu opposite
+                let (settings_left, menu_left) = match handedness {
+                    Handedness::Right => (
+                        screen.x - HEADER_BUTTON_MARGIN - HEADER_BUTTON_WIDTH,
+                        HEADER_BUTTON_MARGIN,
+                    ),
+                    Handedness::Left => (
+                        HEADER_BUTTON_MARGIN,
+                        screen.x - HEADER_BUTTON_MARGIN - HEADER_BUTTON_WIDTH,
+                    ),
+                };
+                Self {
+                    orientation,
+                    handedness,
+                    board,
+                    header: mxcfb_rect {
+                        left: 0,
+                        top: 0,
+                        width: screen.x,
+                        height: board.frame_area().top,
+                    },
+                    title_pos: Point2 {
+                        x: screen.x as i32 / 2,
+                        y: TITLE_BASELINE,
+                    },
+                    title_font_size: TITLE_FONT_SIZE,
+                    score_area: score_area(0, screen.x, SCORE_Y),
+                    score_pos: Point2 {
+                        x: screen.x as i32 / 2,
+                        y: SCORE_Y as i32,
+                    },
+                    status_area: status_area((screen.x - STATUS_WIDTH) / 2, STATUS_WIDTH, STATUS_Y),
+                    status_pos: Point2 {
+                        x: screen.x as i32 / 2,
+                        y: STATUS_Y as i32,
+                    },
+                    settings_button: header_button(settings_left, HEADER_BUTTON_MARGIN),
+                    menu_button: header_button(menu_left, HEADER_BUTTON_MARGIN),
+                }
+            }
+            Orientation::Landscape => {
+                let margin = (screen.y - BOARD_EXTENT) / 2;
+                let board = BoardLayout::new(
+                    board_size,
+                    Point2 {
+                        x: match handedness {
+                            Handedness::Right => margin,
+                            Handedness::Left => screen.x - margin - BOARD_EXTENT,
+                        },
+                        y: margin,
+                    },
+                    BOARD_EXTENT,
+                );
+                // Gap so that clearing the score leaves the frame alone
+                let frame = board.frame_area();
+                let header = match handedness {
+                    Handedness::Right => {
+                        let right = frame.left + frame.width + margin;
+                        mxcfb_rect {
+                            left: right,
+                            top: 0,
+                            width: screen.x - right,
+                            height: screen.y,
+                        }
+                    }
+                    Handedness::Left => mxcfb_rect {
+                        left: 0,
+                        top: 0,
+                        width: frame.left - margin,
+                        height: screen.y,
+                    },
+                };
+                let center_x = (header.left + header.width / 2) as i32;
+                Self {
+                    orientation,
+                    handedness,
+                    board,
+                    header,
+                    title_pos: Point2 {
+                        x: center_x,
+                        y: TITLE_BASELINE,
+                    },
+                    title_font_size: LANDSCAPE_TITLE_FONT_SIZE,
+                    score_area: score_area(header.left, header.width, LANDSCAPE_SCORE_Y),
+                    score_pos: Point2 {
+                        x: center_x,
+                        y: LANDSCAPE_SCORE_Y as i32,
+                    },
+                    status_area: status_area(header.left, header.width, LANDSCAPE_STATUS_Y),
+                    status_pos: Point2 {
+                        x: center_x,
+                        y: LANDSCAPE_STATUS_Y as i32,
+                    },
+                    settings_button: header_button(
+                        center_x as u32 - HEADER_BUTTON_WIDTH / 2,
+                        LANDSCAPE_SETTINGS_BUTTON_Y,
+                    ),
+                    menu_button: header_button(
+                        center_x as u32 - HEADER_BUTTON_WIDTH / 2,
+                        LANDSCAPE_MENU_BUTTON_Y,
+                    ),
+                }
+            }
+        }
+    }
+
+    /// The board of one of the two players of a versus game, in
+    /// their half of the screen: the top and bottom half in portrait,
+    /// the left and right half in landscape. There is no title, score
+    /// or settings button. The status line of the player takes their
+    /// place. Both players share the menu button between the boards.
+    pub fn versus(
+        orientation: Orientation,
+        handedness: Handedness,
+        board_size: usize,
+        player: usize,
+    ) -> Self {
+        let screen = orientation.size();
+        let (half, extent, board_y, status_y, status_width) = match orientation {
+            Orientation::Portrait => (
+                mxcfb_rect {
+                    left: 0,
+                    top: player as u32 * screen.y / 2,
+                    width: screen.x,
+                    height: screen.y / 2,
+                },
+                VERSUS_BOARD_EXTENT,
+                VERSUS_BOARD_Y,
+                VERSUS_STATUS_Y,
+                VERSUS_STATUS_WIDTH,
+            ),
+            Orientation::Landscape => (
+                mxcfb_rect {
+                    left: player as u32 * screen.x / 2,
+                    top: 0,
+                    width: screen.x / 2,
+                    height: screen.y,
+                },
+                LANDSCAPE_VERSUS_BOARD_EXTENT,
+                LANDSCAPE_VERSUS_BOARD_Y,
+                LANDSCAPE_VERSUS_STATUS_Y,
+                LANDSCAPE_VERSUS_BOARD_EXTENT,
+            ),
+        };
+        let board = BoardLayout::new(
+            board_size,
+            Point2 {
+                x: half.left + (half.width - extent) / 2,
+                y: half.top + board_y,
+            },
+            extent,
+        );
+        let center_x = (half.left + half.width / 2) as i32;
+        let status_pos = Point2 {
+            x: center_x,
+            y: (half.top + status_y) as i32,
+        };
+        let status_area = status_area(
+            center_x as u32 - status_width / 2,
+            status_width,
+            half.top + status_y,
+        );
+        let menu_button = match orientation {
+            // Beside the boards on the line between them, opposite the dominant hand
+            Orientation::Portrait => header_button(
+                match handedness {
+                    Handedness::Right => HEADER_BUTTON_MARGIN,
+                    Handedness::Left => screen.x - HEADER_BUTTON_MARGIN - HEADER_BUTTON_WIDTH,
+                },
+                (screen.y - HEADER_BUTTON_HEIGHT) / 2,
+            ),
+            // Below the boards on the line between them
+            Orientation::Landscape => header_button(
+                (screen.x - HEADER_BUTTON_WIDTH) / 2,
+                screen.y - HEADER_BUTTON_MARGIN - HEADER_BUTTON_HEIGHT,
+            ),
+        };
+        Self {
+            orientation,
+            handedness,
+            board,
+            header: mxcfb_rect {
+                left: half.left,
+                top: half.top,
+                width: half.width,
+                height: board.frame_area().top - half.top,
+            },
+            title_pos: status_pos,
+            title_font_size: STATUS_FONT_SIZE,
+            score_area: status_area,
+            score_pos: status_pos,
+            status_area,
+            status_pos,
+            settings_button: mxcfb_rect::invalid(),
+            menu_button,
+        }
+    }
+
+    /// The board and the title, as shown on screenshots.
+    pub fn screenshot_area(&self) -> mxcfb_rect {
+        self.board.frame_area().merge_rect(&self.header)
+    }
+}
+
+fn score_area(left: u32, width: u32, baseline: u32) -> mxcfb_rect {
+    mxcfb_rect {
+        left,
+        top: baseline - SCORE_FONT_SIZE as u32,
+        width,
+        height: SCORE_FONT_SIZE as u32 + 20,
+    }
+}
+
+/// Only as high as the digits, which keeps it clear of
+/// the title above and the board below.
+fn status_area(left: u32, width: u32, baseline: u32) -> mxcfb_rect {
+    let digit_height = (STATUS_FONT_SIZE * 0.8) as u32;
+    mxcfb_rect {
+        left,
+        top: baseline - digit_height,
+        width,
+        height: digit_height + 10,
+    }
+}
+
+fn header_button(left: u32, top: u32) -> mxcfb_rect {
+    mxcfb_rect {
+        left,
+        top,
+        width: HEADER_BUTTON_WIDTH,
+        height: HEADER_BUTTON_HEIGHT,
+    }
+}
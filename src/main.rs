@@ -1,5 +1,34 @@
+mod bench;
+mod board_code;
+mod board_view;
 mod canvas;
+mod cli;
+mod config;
+mod dither;
+mod export;
+mod game;
+mod game_over;
+mod game_screen;
+mod highscore_screen;
+mod highscores;
+mod layout;
+mod menu;
+mod move_queue;
+mod puzzle_screen;
+mod puzzles;
+mod replay;
+mod rules;
+mod screen;
+mod screenshot;
+mod settings_screen;
+mod stats;
+mod stats_screen;
 mod swipe;
+mod tap;
+mod tile_cache;
+mod tiles;
+mod timer;
+mod versus_screen;
 
 #[macro_use]
 extern crate anyhow;
@@ -7,360 +36,306 @@ extern crate anyhow;
 extern crate log;
 
 use anyhow::Result;
-use canvas::{color, mxcfb_rect, Canvas, Point2, Vector2};
-use fxhash::FxHashMap;
-use libremarkable::framebuffer::FramebufferDraw;
-use libremarkable::image::{DynamicImage, RgbImage, Rgba, RgbaImage};
-use libremarkable::input::{ev::EvDevContext, InputDevice, InputEvent};
-use once_cell::sync::Lazy;
-use play_2048::{
-    board::{Board, Direction},
-    game::*,
-};
-use std::time::{Duration, Instant};
+use board_view::BoardView;
+use canvas::Canvas;
+use clap::Parser;
+use cli::{Cli, Command, ConfigArgs, GameArgs, PlayArgs, RenderArgs};
+use config::Config;
+use game::Game;
+use game_screen::GameScreen;
+use highscores::HighScores;
+use layout::Layout;
+use libremarkable::input::ev::EvDevContext;
+use libremarkable::input::gpio::GPIOEvent;
+use libremarkable::input::{InputDevice, InputEvent};
+use menu::Menu;
+use move_queue::{MoveQueue, MOVE_QUEUE_CAPACITY};
+use screen::{Context, Input, Navigator};
+use stats::Stats;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use std::{env, thread};
-use swipe::{Direction as SwipeDirection, Swipe, SwipeTracker, Trigger};
-
-#[rustfmt::skip]
-static NUMBER_IMAGES: Lazy<FxHashMap<u16, RgbaImage>> = Lazy::new(|| {
-    let mut map = FxHashMap::default();
-    map.insert(2, libremarkable::image::load_from_memory(include_bytes!("../res/2.png")).unwrap().to_rgba());
-    map.insert(4, libremarkable::image::load_from_memory(include_bytes!("../res/4.png")).unwrap().to_rgba());
-    map.insert(8, libremarkable::image::load_from_memory(include_bytes!("../res/8.png")).unwrap().to_rgba());
-    map.insert(16, libremarkable::image::load_from_memory(include_bytes!("../res/16.png")).unwrap().to_rgba());
-    map.insert(32, libremarkable::image::load_from_memory(include_bytes!("../res/32.png")).unwrap().to_rgba());
-    map.insert(64, libremarkable::image::load_from_memory(include_bytes!("../res/64.png")).unwrap().to_rgba());
-    map.insert(128, libremarkable::image::load_from_memory(include_bytes!("../res/128.png")).unwrap().to_rgba());
-    map.insert(256, libremarkable::image::load_from_memory(include_bytes!("../res/256.png")).unwrap().to_rgba());
-    map.insert(512, libremarkable::image::load_from_memory(include_bytes!("../res/512.png")).unwrap().to_rgba());
-    map.insert(1024, libremarkable::image::load_from_memory(include_bytes!("../res/1024.png")).unwrap().to_rgba());
-    map.insert(2048, libremarkable::image::load_from_memory(include_bytes!("../res/2048.png")).unwrap().to_rgba());
-    map.insert(4096, libremarkable::image::load_from_memory(include_bytes!("../res/4096.png")).unwrap().to_rgba());
-    map.insert(8192, libremarkable::image::load_from_memory(include_bytes!("../res/8192.png")).unwrap().to_rgba());
-    map.insert(16384, libremarkable::image::load_from_memory(include_bytes!("../res/16384.png")).unwrap().to_rgba());
-    map
-});
-
-#[rustfmt::skip]
-static BG_IMAGES: Lazy<FxHashMap<u16, RgbImage>> = Lazy::new(|| {
-    let mut map = FxHashMap::default();
-    map.insert(2, libremarkable::image::load_from_memory(include_bytes!("../res/bg_2.png")).unwrap().to_rgb());
-    map.insert(4, libremarkable::image::load_from_memory(include_bytes!("../res/bg_4.png")).unwrap().to_rgb());
-    map.insert(8, libremarkable::image::load_from_memory(include_bytes!("../res/bg_8.png")).unwrap().to_rgb());
-    map.insert(16, libremarkable::image::load_from_memory(include_bytes!("../res/bg_16.png")).unwrap().to_rgb());
-    map.insert(32, libremarkable::image::load_from_memory(include_bytes!("../res/bg_32.png")).unwrap().to_rgb());
-    map.insert(64, libremarkable::image::load_from_memory(include_bytes!("../res/bg_64.png")).unwrap().to_rgb());
-    map.insert(128, libremarkable::image::load_from_memory(include_bytes!("../res/bg_128.png")).unwrap().to_rgb());
-    map.insert(256, libremarkable::image::load_from_memory(include_bytes!("../res/bg_256.png")).unwrap().to_rgb());
-    map.insert(512, libremarkable::image::load_from_memory(include_bytes!("../res/bg_512.png")).unwrap().to_rgb());
-    map.insert(1024, libremarkable::image::load_from_memory(include_bytes!("../res/bg_1024.png")).unwrap().to_rgb());
-    map.insert(2048, libremarkable::image::load_from_memory(include_bytes!("../res/bg_2048.png")).unwrap().to_rgb());
-    map.insert(4096, libremarkable::image::load_from_memory(include_bytes!("../res/bg_4096.png")).unwrap().to_rgb());
-    map.insert(8192, libremarkable::image::load_from_memory(include_bytes!("../res/bg_8192.png")).unwrap().to_rgb());
-    map.insert(16384, libremarkable::image::load_from_memory(include_bytes!("../res/bg_16384.png")).unwrap().to_rgb());
-    map
-});
-
-fn duify_image(img: RgbaImage) -> RgbaImage {
-    RgbaImage::from_fn(img.width(), img.height(), |x, y| {
-        let [r, g, b, a] = img.get_pixel(x, y).data;
-        let decided_rgb = if r as u16 + g as u16 + b as u16 > 127 * 3 {
-            255
-        } else {
-            0
-        };
-        let decided_alpha = if a > 127 { 255 } else { 0 };
-        Rgba([decided_rgb, decided_rgb, decided_rgb, decided_alpha])
-    })
-}
-
-impl From<SwipeDirection> for Direction {
-    fn from(swipe_dir: SwipeDirection) -> Self {
-        match swipe_dir {
-            SwipeDirection::Up => Direction::Up,
-            SwipeDirection::Right => Direction::Right,
-            SwipeDirection::Down => Direction::Down,
-            SwipeDirection::Left => Direction::Left,
-        }
-    }
-}
-
-const CELL_SIZE: u32 = 320;
-const CELL_MARGIN: u32 = 4;
+use swipe::{Direction as SwipeDirection, RegionSwipeTracker, Swipe};
+use tap::TapTracker;
+use tiles::Theme;
+use timer::TICK_INTERVAL;
 
 fn main() -> Result<()> {
+    let startup = Instant::now();
     if env::var("RUST_LOG").is_err() {
         env::set_var("RUST_LOG", "INFO");
     }
     env_logger::builder().format_timestamp_millis().init();
 
-    let mut canvas = Canvas::new();
-    canvas.clear();
-    canvas.update_full();
+    let cli = Cli::parse();
+    match cli
+        .command
+        .unwrap_or_else(|| Command::Play(PlayArgs::default()))
+    {
+        Command::Play(args) => play(&args, startup),
+        Command::Replay(args) => replay::run(&args, Config::load()?),
+        Command::Export(args) => export::run(&args),
+        Command::Stats => print_stats(),
+        Command::Bench(args) => bench::run(&args, Config::load()?),
+        Command::Render(args) => render(&args, Config::load()?),
+        Command::Config(args) => print_config(&args, &Config::load()?),
+    }
+}
 
-    draw_background(&mut canvas);
+fn play(args: &PlayArgs, startup: Instant) -> Result<()> {
+    // A broken config file shouldn't keep the game from starting
+    let mut config = Config::load().unwrap_or_else(|e| {
+        error!("Using the default config: {:?}", e);
+        Config::default()
+    });
+    args.game.apply(&mut config);
+    args.display.apply(&mut config);
+    let game = start_game(&args.game, &config)?;
+    info!(
+        "Using theme {:?} with dithering {:?} in {:?}",
+        config.theme, config.dithering, config.orientation
+    );
+    if config.theme == Theme::Images {
+        tiles::decode_images_in_background(config.dithering);
+    }
 
-    // Initialize game
-    let mut game = GameBuilder::default().build();
-    let mut board = game.board;
-    draw_changed_cells(&mut canvas, None, board)?;
-    //game.board.move_to(direction)
+    info!("Using move queue policy {:?}", config.move_queue);
+    let move_queue = Arc::new(MoveQueue::new(config.move_queue, MOVE_QUEUE_CAPACITY));
+    let (input_tx, input_rx) = std::sync::mpsc::channel();
+    let render_thread = {
+        let move_queue = move_queue.clone();
+        let input_tx = input_tx.clone();
+        thread::spawn(move || {
+            let result = run_game(&move_queue, game, config, startup);
+            // Lets the input loop know that nobody is rendering anymore
+            move_queue.close();
+            // Wakes it up in case nothing gets touched anymore
+            let _ = input_tx.send(InputEvent::Unknown {});
+            result
+        })
+    };
+
+    // The input loop needs the swipe settings. Changes
+    // to the file replace the command line options.
+    let shared_config = Arc::new(Mutex::new(config));
+    {
+        let move_queue = move_queue.clone();
+        let shared_config = shared_config.clone();
+        config::watch(move |config| {
+            *shared_config.lock().unwrap() = config;
+            move_queue.set_policy(config.move_queue);
+            move_queue.force_push(Input::Config(config));
+        });
+    }
 
     // Input loop
-    let (input_tx, input_rx) = std::sync::mpsc::channel();
-    EvDevContext::new(InputDevice::Multitouch, input_tx).start();
-    let mut swipe_tracker = SwipeTracker::new();
-    let swipes = &[
-        Swipe {
-            direction: SwipeDirection::Up,
-            trigger: Trigger::Completed,
-        },
-        Swipe {
-            direction: SwipeDirection::Right,
-            trigger: Trigger::Completed,
-        },
-        Swipe {
-            direction: SwipeDirection::Down,
-            trigger: Trigger::Completed,
-        },
-        Swipe {
-            direction: SwipeDirection::Left,
-            trigger: Trigger::Completed,
-        },
-    ];
+    EvDevContext::new(InputDevice::Multitouch, input_tx.clone()).start();
+    EvDevContext::new(InputDevice::GPIO, input_tx).start();
+    // Each player of a versus game swipes on their own half
+    let mut swipe_tracker = RegionSwipeTracker::halves(config.swipe);
+    let mut tap_tracker = TapTracker::new();
 
     for event in input_rx {
+        if move_queue.is_closed() {
+            break;
+        }
+        let config = *shared_config.lock().unwrap();
         match event {
             InputEvent::MultitouchEvent { event } => {
-                for swipe in swipe_tracker.detect(event, swipes) {
-                    info!("Swiped {:?}", swipe.direction);
-                    let start = Instant::now();
-                    let last = board;
-                    game.play(swipe.direction.into());
-                    draw_changed_cells(&mut canvas, Some(last), game.board)?;
-                    if last != game.board && game.board.count_empty_tiles() > 0 {
-                        thread::sleep(Duration::from_millis(350).saturating_sub(start.elapsed()));
-                        let last = game.board;
-                        game.populate_new_tile();
-                        draw_changed_cells(&mut canvas, Some(last), game.board)?;
+                swipe_tracker.set_settings(config.swipe);
+                let swipes = [
+                    SwipeDirection::Up,
+                    SwipeDirection::Right,
+                    SwipeDirection::Down,
+                    SwipeDirection::Left,
+                ]
+                .map(|direction| Swipe {
+                    direction,
+                    trigger: config.swipe.trigger(),
+                });
+                if let Some((half, swipe)) = swipe_tracker.detect(event, &swipes) {
+                    if move_queue.push(Input::Swipe(swipe.direction, half), half) {
+                        info!("Swiped {:?}", swipe.direction);
+                    } else {
+                        info!("Swiped {:?} (dropped)", swipe.direction);
                     }
-                    board = game.board;
+                }
+                if let Some(pos) = tap_tracker.detect(event) {
+                    debug!("Tapped at {:?}", pos);
+                    move_queue.force_push(Input::Tap(pos));
                 }
             }
+            InputEvent::GPIO {
+                event: GPIOEvent::Press { button },
+            } => {
+                debug!("Pressed {:?}", button);
+                move_queue.force_push(Input::Button(button));
+            }
+            InputEvent::GPIO { .. } => {}
             _ => {
                 bail!("Unexpected input event type!")
             }
         }
     }
 
+    render_thread
+        .join()
+        .map_err(|_| anyhow!("Render thread panicked!"))??;
     info!("Bye!");
     Ok(())
 }
 
-fn full_area() -> mxcfb_rect {
-    let middle_x = libremarkable::framebuffer::common::DISPLAYWIDTH / 2;
-    let middle_y = libremarkable::framebuffer::common::DISPLAYHEIGHT / 2;
+/// Owns the screen and the game. Passes the input as it
+/// comes in through the queue on to the screens.
+fn run_game(
+    move_queue: &MoveQueue<Input>,
+    game: Game,
+    config: Config,
+    startup: Instant,
+) -> Result<()> {
+    let mut ctx = Context {
+        canvas: Canvas::new(config.orientation),
+        view: BoardView::new(
+            Layout::new(config.orientation, config.handedness, game.size),
+            &config,
+            game.rules,
+        ),
+        config,
+    };
+    // Continue gets back to the game below the start screen
+    let can_continue = game.in_progress();
+    let mut navigator = Navigator::new(
+        &mut ctx,
+        vec![
+            Box::new(GameScreen::new(game)),
+            Box::new(Menu::main(can_continue)),
+        ],
+    )?;
+    info!("First frame drawn after {:?}", startup.elapsed());
+    move_queue.set_split_sources(navigator.splits_swipes());
 
-    mxcfb_rect {
-        left: middle_x as u32 - CELL_SIZE * 2 - CELL_MARGIN * 4,
-        top: middle_y as u32 - CELL_SIZE * 2 - CELL_MARGIN * 4,
-        width: CELL_SIZE * 4 + CELL_MARGIN * 8,
-        height: CELL_SIZE * 4 + CELL_MARGIN * 8,
-    }
-}
+    // Render the tiles while the player looks at the menu
+    ctx.view.prerender(&mut ctx.canvas);
+    info!("Startup took {:?}", startup.elapsed());
 
-fn draw_changed_cells(canvas: &mut Canvas, last: Option<Board>, current: Board) -> Result<()> {
-    debug!("Board: {}", current);
-    let start = Instant::now();
-    for i in 0..16 {
-        let draw_value = {
-            if let Some(last) = last {
-                let last_val = last.get_value(i);
-                let cur_val = current.get_value(i);
-                if last_val == cur_val {
-                    continue;
-                } else {
-                    cur_val
-                }
-            } else {
-                current.get_value(i)
+    let mut next_tick = Instant::now() + TICK_INTERVAL;
+    loop {
+        // Ticks come in time even while the input keeps coming
+        let input = if Instant::now() < next_tick {
+            move_queue.pop_until(next_tick)
+        } else {
+            None
+        };
+        let input = match input {
+            Some(input) => input,
+            None if move_queue.is_closed() => break,
+            None => {
+                next_tick = Instant::now() + TICK_INTERVAL;
+                Input::Tick
             }
         };
-        draw_cell(canvas, i as u32 % 4, i as u32 / 4, draw_value)?;
+        let showing = navigator.handle_input(&mut ctx, input)?;
+        move_queue.set_split_sources(navigator.splits_swipes());
+        move_queue.done();
+        if !showing {
+            break;
+        }
     }
-    debug!("Update took {:?}", start.elapsed());
     Ok(())
 }
 
-fn draw_cell(canvas: &mut Canvas, x: u32, y: u32, number: u16) -> Result<()> {
-    /*let ref text = if number == 0 {
-        "".to_owned()
-    } else {
-        number.to_string()
-    };*/
-    let cell_area = cell_area(x, y, false)?;
-
-    // Clear any previous content
-    canvas.fill_rect(
-        Point2 {
-            x: Some(cell_area.left as i32),
-            y: Some(cell_area.top as i32),
-        },
-        Vector2 {
-            x: cell_area.width,
-            y: cell_area.height,
-        },
-        color::WHITE,
-    );
-
-    /*
-    // Get estimated size
-    let text_size = canvas.framebuffer_mut().draw_text(
-        Point2 {
-            x: 0f32,
-            y: 500f32, // Whatever
-        },
-        text,
-        100.0f32,
-        color::BLACK,
-        true,
-    );
-
-    // Draw centered (kinda) in cell_area
-    canvas.draw_text(
-        Point2 {
-            x: Some((cell_area.left + (cell_area.width - text_size.width) / 2) as i32),
-            y: Some((cell_area.top + (cell_area.height - text_size.height) / 2 + 60) as i32),
-        },
-        text,
-        100.0f32,
-    );*/
-
-    if number > 0 {
-        let bg = BG_IMAGES
-            .get(&number)
-            .ok_or(anyhow!("No bg image for number found!"))?;
-        let (img_width, img_height) = bg.dimensions();
-        let bg = DynamicImage::ImageRgb8(bg.clone());
-        canvas.draw_image(
-            Point2 {
-                x: cell_area.left + (cell_area.width - img_width) / 2,
-                y: cell_area.top + (cell_area.height - img_height) / 2,
+/// The first game as given on the command line.
+fn start_game(args: &GameArgs, config: &Config) -> Result<Game> {
+    // The options might not go together with the config file
+    config.validate()?;
+    let (mode, rules) = (config.mode, config.rules());
+    let seed = args.seed.unwrap_or_else(rand::random);
+    let mut game = match &args.board {
+        Some(board) => {
+            if let Some(size) = args.size {
+                ensure!(
+                    size == board.size,
+                    "--size {} doesn't match the {}x{} board",
+                    size,
+                    board.size,
+                    board.size
+                );
             }
-            .cast()
-            .unwrap(),
-            &bg,
-            false,
-        );
+            info!("Continuing from board {:?} with seed {}", board, seed);
+            Game::with_cells(
+                mode,
+                rules,
+                board.size,
+                seed,
+                board.cells(rules)?,
+                board.score,
+            )
+        }
+        None => {
+            info!("Starting new game with seed {}", seed);
+            Game::new(mode, rules, config.board_size, seed, config.start_walls())
+        }
+    };
+    game.time_limit = config.time_limit();
+    Ok(game)
+}
 
-        let img = NUMBER_IMAGES
-            .get(&number)
-            .ok_or(anyhow!("No image for number found!"))?;
-        let (img_width, img_height) = img.dimensions();
-        let img = DynamicImage::ImageRgba8(img.clone());
-        canvas.draw_image(
-            Point2 {
-                x: cell_area.left + (cell_area.width - img_width) / 2,
-                y: cell_area.top + (cell_area.height - img_height) / 2,
-            }
-            .cast()
-            .unwrap(),
-            &img,
-            true,
-        );
+/// Draws the game once, either on the screen or into a PNG.
+fn render(args: &RenderArgs, mut config: Config) -> Result<()> {
+    args.game.apply(&mut config);
+    args.display.apply(&mut config);
+    let game = start_game(&args.game, &config)?;
+    let mut canvas = match args.headless {
+        Some(_) => Canvas::headless(config.orientation)?,
+        None => Canvas::new(config.orientation),
+    };
+    let mut view = BoardView::new(
+        Layout::new(config.orientation, config.handedness, game.size),
+        &config,
+        game.rules,
+    );
+    let start = Instant::now();
+    view.draw_game(&mut canvas, &game)?;
+    info!("Drew the board in {:?}", start.elapsed());
+    if let Some(time_limit) = game.time_limit {
+        view.draw_status(&mut canvas, &timer::format(time_limit));
+    }
+    match &args.headless {
+        Some(path) => {
+            canvas.save_png(path)?;
+            info!("Saved board to {:?}", path);
+        }
+        None => canvas.wait_for_all_updates(),
     }
-
-    canvas.update_partial(&cell_area);
-    debug!("Cell {},{} => {}", x, y, number);
     Ok(())
 }
 
-fn cell_area(x: u32, y: u32, include_margin: bool) -> Result<mxcfb_rect> {
-    ensure!(x < 4, "Only 0-3 allowed for X!");
-    ensure!(y < 4, "Only 0-3 allowed for Y!");
+fn print_stats() -> Result<()> {
+    let records = stats::load_games()?;
+    let stats = Stats::from_records(&records);
+    for line in stats.summary() {
+        println!("{}", line);
+    }
+    println!("Highest tile per game:");
+    for (tile, games) in stats.max_tiles.iter().rev() {
+        println!("{:>8}: {}", tile, games);
+    }
 
-    let full_area = full_area();
-    if include_margin {
-        Ok(mxcfb_rect {
-            left: full_area.left + x * CELL_SIZE + x * 2 * CELL_MARGIN + CELL_MARGIN,
-            top: full_area.top + y * CELL_SIZE + y * 2 * CELL_MARGIN + CELL_MARGIN,
-            width: CELL_SIZE + CELL_MARGIN,
-            height: CELL_SIZE + CELL_MARGIN,
-        })
-    } else {
-        Ok(mxcfb_rect {
-            left: full_area.left + x * CELL_SIZE + x * 2 * CELL_MARGIN,
-            top: full_area.top + y * CELL_SIZE + y * 2 * CELL_MARGIN,
-            width: CELL_SIZE,
-            height: CELL_SIZE,
-        })
+    let highscores = HighScores::load()?;
+    for (name, table) in highscores.tables() {
+        println!("High scores {}:", name);
+        for (rank, entry) in table.iter().enumerate() {
+            println!("{:>4}. {:<3} {:>8}", rank + 1, entry.name, entry.score);
+        }
     }
+    Ok(())
 }
 
-fn draw_background(canvas: &mut Canvas) {
-    // Title
-    let update_area = canvas.draw_text(
-        Point2 {
-            x: None,
-            y: Some(175),
-        },
-        "inky-2048",
-        125.0f32,
-    );
-    canvas.update_partial(&update_area);
-
-    // Field background
-    const OUTER_MARGIN: u32 = 4;
-    let full_area = full_area();
-    let update_area = canvas.draw_rect(
-        Point2 {
-            x: Some(full_area.left as i32 - OUTER_MARGIN as i32),
-            y: Some(full_area.top as i32 - OUTER_MARGIN as i32),
-        },
-        Vector2 {
-            x: full_area.width + OUTER_MARGIN,
-            y: full_area.height + OUTER_MARGIN,
-        },
-        3,
-    );
-
-    for i in 0..3 {
-        let vertical_x = update_area.left + (CELL_SIZE + CELL_MARGIN * 2) * (i + 1) - 1;
-        canvas.framebuffer_mut().draw_line(
-            Point2 {
-                x: vertical_x,
-                y: update_area.top,
-            }
-            .cast()
-            .unwrap(),
-            Point2 {
-                x: vertical_x,
-                y: update_area.top + update_area.height,
-            }
-            .cast()
-            .unwrap(),
-            3,
-            color::BLACK,
-        );
-        let horizontal_y = update_area.top + (CELL_SIZE + CELL_MARGIN * 2) * (i + 1) - 1;
-        canvas.framebuffer_mut().draw_line(
-            Point2 {
-                x: update_area.left,
-                y: horizontal_y,
-            }
-            .cast()
-            .unwrap(),
-            Point2 {
-                x: update_area.left + update_area.width,
-                y: horizontal_y,
-            }
-            .cast()
-            .unwrap(),
-            3,
-            color::BLACK,
-        );
+fn print_config(args: &ConfigArgs, config: &Config) -> Result<()> {
+    print!("{}", toml::to_string_pretty(config)?);
+    if args.save {
+        config.save()?;
+        info!("Saved to {:?}", config::path());
     }
-
-    canvas.update_partial(&update_area);
-    debug!("Background drawn.");
+    Ok(())
 }
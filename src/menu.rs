@@ -0,0 +1,328 @@
+use crate::canvas::{color, mxcfb_rect, Canvas, Point2, Refresh, Vector2};
+use crate::config::Config;
+use crate::game::Mode;
+use crate::game_screen::GameScreen;
+use crate::puzzle_screen::PuzzleSelect;
+use crate::puzzles::Pack;
+use crate::screen::{Context, Input, Screen, Transition};
+use crate::settings_screen::SettingsScreen;
+use crate::stats_screen::StatsScreen;
+use crate::versus_screen::VersusScreen;
+use anyhow::Result;
+use clap::ValueEnum;
+use libremarkable::input::gpio::PhysicalButton;
+
+const TITLE_FONT_SIZE: f32 = 100.0;
+const ITEM_FONT_SIZE: f32 = 60.0;
+/// Items get lower should they not fit otherwise
+const MAX_ITEM_HEIGHT: u32 = 150;
+/// Top of the first item
+const ITEMS_TOP: u32 = 300;
+/// Width of the lines drawn by Canvas::draw_box_button()
+const ITEM_LINE_WIDTH: u32 = 5;
+/// Distance of the focus frame to the lines of its item
+const FOCUS_INSET: u32 = 15;
+const FOCUS_LINE_WIDTH: u32 = 5;
+const HINT_FONT_SIZE: f32 = 35.0;
+/// Baseline of the hint below the last item
+const HINT_OFFSET: u32 = 100;
+/// Space kept free for the hint at the bottom of the screen
+const HINT_SPACE: u32 = 150;
+
+/// Something that can be picked from a menu.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MenuItem {
+    /// Back to the game below the menu
+    Continue,
+    NewGame,
+    /// Shows the modes to start a new game in
+    Modes,
+    /// A new game in this mode
+    Mode(Mode),
+    /// Shows the puzzles to pick from
+    Puzzles,
+    /// The puzzle with this index in the pack, again
+    Retry(usize),
+    /// The puzzle with this index in the pack, after the one played
+    NextPuzzle(usize),
+    /// A versus game of two players on one device
+    Versus,
+    /// Another versus game after the one played
+    Rematch,
+    Stats,
+    Settings,
+    /// Back to the start screen
+    Back,
+    Quit,
+}
+
+impl MenuItem {
+    fn label(self, config: &Config) -> String {
+        match self {
+            MenuItem::Continue => "Continue".to_owned(),
+            MenuItem::NewGame => "New game".to_owned(),
+            MenuItem::Modes => "Modes".to_owned(),
+            MenuItem::Mode(Mode::CustomBase) => format!("Base {}", config.base),
+            MenuItem::Mode(Mode::TimeAttack) => {
+                format!("Time attack ({} min)", config.time_limit_minutes)
+            }
+            MenuItem::Mode(mode) => format!("{:?}", mode),
+            MenuItem::Puzzles => "Puzzles".to_owned(),
+            MenuItem::Retry(_) => "Try again".to_owned(),
+            MenuItem::NextPuzzle(_) => "Next puzzle".to_owned(),
+            MenuItem::Versus => "Versus".to_owned(),
+            MenuItem::Rematch => "Rematch".to_owned(),
+            MenuItem::Stats => "Statistics".to_owned(),
+            MenuItem::Settings => "Settings".to_owned(),
+            MenuItem::Back => "Back".to_owned(),
+            MenuItem::Quit => "Quit".to_owned(),
+        }
+    }
+}
+
+/// A list of items that can be tapped or walked through with the
+/// hardware buttons (left and right to move, middle to pick).
+pub struct Menu {
+    title: String,
+    items: Vec<MenuItem>,
+    rows: Vec<mxcfb_rect>,
+    /// Index of the item the middle button picks
+    focus: usize,
+    /// Why the picked item can't be done. Shown instead of the hint.
+    error: Option<String>,
+}
+
+impl Menu {
+    /// The start screen. Continue is only offered if there is a game
+    /// in progress right below the menu.
+    pub fn main(can_continue: bool) -> Self {
+        let mut items = vec![
+            MenuItem::NewGame,
+            MenuItem::Modes,
+            MenuItem::Puzzles,
+            MenuItem::Versus,
+            MenuItem::Stats,
+            MenuItem::Settings,
+            MenuItem::Quit,
+        ];
+        if can_continue {
+            items.insert(0, MenuItem::Continue);
+        }
+        Self::new("inky-2048".to_owned(), items)
+    }
+
+    /// All modes a new game can be started in.
+    pub fn modes() -> Self {
+        let mut items: Vec<MenuItem> = Mode::value_variants()
+            .iter()
+            .map(|mode| MenuItem::Mode(*mode))
+            .collect();
+        items.push(MenuItem::Back);
+        Self::new("Modes".to_owned(), items)
+    }
+
+    /// What to do after a puzzle. Back gets to the list of puzzles.
+    /// `error` tells why the progress wasn't saved.
+    pub fn puzzle_over(
+        title: String,
+        index: usize,
+        next: Option<usize>,
+        error: Option<String>,
+    ) -> Self {
+        let mut items = vec![MenuItem::Retry(index), MenuItem::Back];
+        if let Some(next) = next {
+            items.insert(0, MenuItem::NextPuzzle(next));
+        }
+        Self {
+            error,
+            ..Self::new(title, items)
+        }
+    }
+
+    /// What to do after a versus game. Back gets to the start screen.
+    pub fn versus_over(title: String) -> Self {
+        Self::new(title, vec![MenuItem::Rematch, MenuItem::Back])
+    }
+
+    fn new(title: String, items: Vec<MenuItem>) -> Self {
+        Self {
+            title,
+            items,
+            rows: Vec::new(),
+            focus: 0,
+            error: None,
+        }
+    }
+
+    /// Returns the tapped item.
+    fn tapped_item(&self, pos: Point2<u16>) -> Option<MenuItem> {
+        let index = self
+            .rows
+            .iter()
+            .position(|row| Canvas::is_hitting(pos, *row))?;
+        Some(self.items[index])
+    }
+
+    /// Moves the focus or returns the focused item.
+    fn pressed_item(&mut self, canvas: &mut Canvas, button: PhysicalButton) -> Option<MenuItem> {
+        let len = self.items.len();
+        let focus = match button {
+            PhysicalButton::LEFT => (self.focus + len - 1) % len,
+            PhysicalButton::RIGHT => (self.focus + 1) % len,
+            PhysicalButton::MIDDLE => return Some(self.items[self.focus]),
+            PhysicalButton::POWER | PhysicalButton::WAKEUP => return None,
+        };
+        let last_area = self.draw_focus(canvas, color::WHITE);
+        self.focus = focus;
+        let area = self.draw_focus(canvas, color::BLACK);
+        canvas.update_partial(&last_area, Refresh::Normal);
+        canvas.update_partial(&area, Refresh::Normal);
+        None
+    }
+
+    /// Draws (or erases) the frame inside the focused item.
+    /// Returns its area.
+    fn draw_focus(&self, canvas: &mut Canvas, clr: color) -> mxcfb_rect {
+        let row = self.rows[self.focus];
+        let inset = ITEM_LINE_WIDTH + FOCUS_INSET;
+        let area = mxcfb_rect {
+            left: row.left + inset,
+            top: row.top + inset,
+            width: row.width - 2 * inset,
+            height: row.height - 2 * inset,
+        };
+        let sides = [
+            // Top and bottom
+            (area.left, area.top, area.width, FOCUS_LINE_WIDTH),
+            (
+                area.left,
+                area.top + area.height - FOCUS_LINE_WIDTH,
+                area.width,
+                FOCUS_LINE_WIDTH,
+            ),
+            // Left and right
+            (area.left, area.top, FOCUS_LINE_WIDTH, area.height),
+            (
+                area.left + area.width - FOCUS_LINE_WIDTH,
+                area.top,
+                FOCUS_LINE_WIDTH,
+                area.height,
+            ),
+        ];
+        for (left, top, width, height) in sides {
+            canvas.fill_rect(
+                Point2 {
+                    x: Some(left as i32),
+                    y: Some(top as i32),
+                },
+                Vector2 {
+                    x: width,
+                    y: height,
+                },
+                clr,
+            );
+        }
+        area
+    }
+}
+
+impl Screen for Menu {
+    fn draw(&mut self, ctx: &mut Context) -> Result<()> {
+        let canvas = &mut ctx.canvas;
+        canvas.clear();
+        canvas.draw_text(
+            Point2 {
+                x: None,
+                y: Some(175),
+            },
+            &self.title,
+            TITLE_FONT_SIZE,
+        );
+        let item_height = ((canvas.height() - ITEMS_TOP - HINT_SPACE) / self.items.len() as u32)
+            .min(MAX_ITEM_HEIGHT);
+        self.rows = (0..self.items.len())
+            .map(|i| {
+                let top = ITEMS_TOP + i as u32 * item_height;
+                canvas.draw_box_button(
+                    top as i32,
+                    item_height,
+                    &self.items[i].label(&ctx.config),
+                    ITEM_FONT_SIZE,
+                )
+            })
+            .collect();
+        self.draw_focus(canvas, color::BLACK);
+
+        let hint_y = ITEMS_TOP + self.items.len() as u32 * item_height + HINT_OFFSET;
+        canvas.draw_text(
+            Point2 {
+                x: None,
+                y: Some(hint_y as i32),
+            },
+            self.error
+                .as_deref()
+                .unwrap_or("Buttons: left and right to move, middle to pick"),
+            HINT_FONT_SIZE,
+        );
+        canvas.update_full(Refresh::Quality);
+        Ok(())
+    }
+
+    fn handle_input(&mut self, ctx: &mut Context, input: Input) -> Result<Transition> {
+        let item = match input {
+            Input::Tap(pos) => self.tapped_item(pos),
+            Input::Button(button) => self.pressed_item(&mut ctx.canvas, button),
+            Input::Config(_) => {
+                ctx.canvas.set_orientation(ctx.config.orientation);
+                self.draw(ctx)?;
+                None
+            }
+            _ => None,
+        };
+        let item = match item {
+            Some(item) => item,
+            None => return Ok(Transition::Stay),
+        };
+        debug!("Picked {:?}", item);
+        Ok(match item {
+            MenuItem::Continue | MenuItem::Back => Transition::Pop,
+            MenuItem::NewGame => Transition::Reset(Box::new(GameScreen::new_game(&ctx.config))),
+            MenuItem::Modes => Transition::Push(Box::new(Menu::modes())),
+            MenuItem::Mode(mode) => {
+                // The base might not fit the board in this mode
+                let config = Config { mode, ..ctx.config };
+                match config.validate() {
+                    Ok(()) => {
+                        ctx.config = config;
+                        Transition::Reset(Box::new(GameScreen::new_game(&ctx.config)))
+                    }
+                    Err(e) => {
+                        warn!("Can't start a game in {:?}: {:?}", mode, e);
+                        self.error = Some(format!("{:#}", e));
+                        self.draw(ctx)?;
+                        Transition::Stay
+                    }
+                }
+            }
+            MenuItem::Puzzles => Transition::Push(Box::new(PuzzleSelect::new())),
+            MenuItem::Retry(index) | MenuItem::NextPuzzle(index) => {
+                match Pack::load().and_then(|pack| GameScreen::puzzle(pack, index)) {
+                    Ok(screen) => Transition::Replace(Box::new(screen)),
+                    Err(e) => {
+                        error!("Failed to start the puzzle: {:?}", e);
+                        Transition::Stay
+                    }
+                }
+            }
+            MenuItem::Versus => Transition::Push(Box::new(VersusScreen::new(&ctx.config))),
+            MenuItem::Rematch => Transition::Replace(Box::new(VersusScreen::new(&ctx.config))),
+            MenuItem::Stats => Transition::Push(Box::new(StatsScreen::new())),
+            MenuItem::Settings => Transition::Push(Box::new(SettingsScreen::new(ctx.config))),
+            MenuItem::Quit => Transition::Quit,
+        })
+    }
+
+    fn uses_buttons(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::VecDeque;
+use std::env;
+use std::sync::{Condvar, Mutex};
+use std::time::Instant;
+
+/// Maximum amount of moves waiting to be rendered.
+pub const MOVE_QUEUE_CAPACITY: usize = 4;
+
+/// What happens to moves made while the previous ones
+/// are still being rendered.
+#[derive(Debug, Copy, Clone, PartialEq, clap::ValueEnum)]
+pub enum QueuePolicy {
+    /// Every move will be done (up to MOVE_QUEUE_CAPACITY
+    /// waiting ones). Any more are dropped.
+    #[clap(name = "all")]
+    QueueAll,
+    /// Only the latest move will be done once rendering
+    /// finished. Older waiting moves from the same source
+    /// are replaced.
+    #[clap(name = "last")]
+    KeepLast,
+    /// Moves are ignored entirely while rendering.
+    #[clap(name = "drop")]
+    DropWhileBusy,
+}
+
+impl QueuePolicy {
+    /// Reads the policy from INKY_2048_MOVE_QUEUE (all, last or drop).
+    pub fn from_env() -> Self {
+        match env::var("INKY_2048_MOVE_QUEUE").as_deref() {
+            Ok("all") => QueuePolicy::QueueAll,
+            Ok("last") | Err(_) => QueuePolicy::KeepLast,
+            Ok("drop") => QueuePolicy::DropWhileBusy,
+            Ok(other) => {
+                warn!("Unknown move queue policy {:?}. Using last.", other);
+                QueuePolicy::KeepLast
+            }
+        }
+    }
+}
+
+struct State<T> {
+    policy: QueuePolicy,
+    /// Items with their source. None for forced ones.
+    queue: VecDeque<(T, Option<usize>)>,
+    /// See set_split_sources()
+    split_sources: bool,
+    /// A move was taken and is not done yet. Forced items
+    /// don't count.
+    busy: bool,
+    closed: bool,
+}
+
+/// Bounded queue between the input handling and the rendering thread.
+pub struct MoveQueue<T> {
+    capacity: usize,
+    state: Mutex<State<T>>,
+    available: Condvar,
+}
+
+impl<T> State<T> {
+    /// Waiting items that aren't forced.
+    fn waiting_moves(&self) -> usize {
+        self.queue
+            .iter()
+            .filter(|(_, source)| source.is_some())
+            .count()
+    }
+}
+
+impl<T> MoveQueue<T> {
+    pub fn new(policy: QueuePolicy, capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(State {
+                policy,
+                queue: VecDeque::with_capacity(capacity),
+                split_sources: false,
+                busy: false,
+                closed: false,
+            }),
+            available: Condvar::new(),
+        }
+    }
+
+    /// Returns false if the item got dropped due to the policy.
+    /// Moves from different sources (like the halves of the
+    /// touchscreen) only stay apart while the sources are split.
+    pub fn push(&self, item: T, source: usize) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return false;
+        }
+        let source = if state.split_sources { source } else { 0 };
+
+        let accepted = match state.policy {
+            QueuePolicy::QueueAll => state.waiting_moves() < self.capacity,
+            QueuePolicy::KeepLast => {
+                state.queue.retain(|(_, from)| *from != Some(source));
+                true
+            }
+            QueuePolicy::DropWhileBusy => !state.busy && state.waiting_moves() == 0,
+        };
+        if accepted {
+            state.queue.push_back((item, Some(source)));
+            self.available.notify_one();
+        }
+        accepted
+    }
+
+    /// Whether moves from different sources don't replace each
+    /// other under KeepLast. Only versus games need that.
+    /// Applies to moves pushed from now on.
+    pub fn set_split_sources(&self, split: bool) {
+        self.state.lock().unwrap().split_sources = split;
+    }
+
+    /// Applies to moves pushed from now on.
+    pub fn set_policy(&self, policy: QueuePolicy) {
+        self.state.lock().unwrap().policy = policy;
+    }
+
+    /// Adds the item regardless of the policy. Meant for input
+    /// that should never be dropped, like taps on buttons.
+    /// Forced items won't be replaced by KeepLast either.
+    pub fn force_push(&self, item: T) {
+        let mut state = self.state.lock().unwrap();
+        if !state.closed {
+            state.queue.push_back((item, None));
+            self.available.notify_one();
+        }
+    }
+
+    /// Blocks until an item is available or the deadline passed.
+    /// Every taken item has to be marked with done() once it's
+    /// handled. Returns None if nothing came in by the deadline
+    /// and once the queue got closed.
+    pub fn pop_until(&self, deadline: Instant) -> Option<T> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.closed {
+                return None;
+            }
+            if let Some((item, source)) = state.queue.pop_front() {
+                if source.is_some() {
+                    state.busy = true;
+                }
+                return Some(item);
+            }
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout.is_zero() {
+                return None;
+            }
+            state = self.available.wait_timeout(state, timeout).unwrap().0;
+        }
+    }
+
+    pub fn done(&self) {
+        self.state.lock().unwrap().busy = false;
+    }
+
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.available.notify_all();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Everything waiting in the queue.
+    fn drain<T>(queue: &MoveQueue<T>) -> Vec<T> {
+        let mut items = Vec::new();
+        while let Some(item) = queue.pop_until(Instant::now()) {
+            queue.done();
+            items.push(item);
+        }
+        items
+    }
+
+    #[test]
+    fn queue_all_keeps_moves_up_to_the_capacity() {
+        let queue = MoveQueue::new(QueuePolicy::QueueAll, 3);
+        assert!(queue.push(1, 0));
+        assert!(queue.push(2, 0));
+        assert!(queue.push(3, 0));
+        assert!(!queue.push(4, 0));
+        assert_eq!(drain(&queue), vec![1, 2, 3]);
+        assert!(queue.push(5, 0));
+        assert_eq!(drain(&queue), vec![5]);
+    }
+
+    #[test]
+    fn keep_last_replaces_waiting_moves() {
+        let queue = MoveQueue::new(QueuePolicy::KeepLast, 3);
+        assert!(queue.push(1, 0));
+        assert!(queue.push(2, 0));
+        assert!(queue.push(3, 0));
+        assert_eq!(drain(&queue), vec![3]);
+    }
+
+    #[test]
+    fn keep_last_keeps_split_sources_apart() {
+        let queue = MoveQueue::new(QueuePolicy::KeepLast, 3);
+        assert!(queue.push(1, 0));
+        assert!(queue.push(2, 1));
+        assert_eq!(drain(&queue), vec![2]);
+        queue.set_split_sources(true);
+        assert!(queue.push(1, 0));
+        assert!(queue.push(2, 1));
+        assert!(queue.push(3, 0));
+        assert_eq!(drain(&queue), vec![2, 3]);
+    }
+
+    #[test]
+    fn drop_while_busy_ignores_moves_until_done() {
+        let queue = MoveQueue::new(QueuePolicy::DropWhileBusy, 3);
+        assert!(queue.push(1, 0));
+        assert!(!queue.push(2, 0));
+        assert_eq!(queue.pop_until(Instant::now()), Some(1));
+        assert!(!queue.push(3, 0));
+        queue.done();
+        assert!(queue.push(4, 0));
+        assert_eq!(drain(&queue), vec![4]);
+    }
+
+    #[test]
+    fn forced_items_bypass_the_policy() {
+        let queue = MoveQueue::new(QueuePolicy::KeepLast, 1);
+        queue.force_push(1);
+        queue.push(2, 0);
+        queue.force_push(3);
+        queue.push(4, 0);
+        assert_eq!(drain(&queue), vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn forced_items_dont_count_as_moves() {
+        let queue = MoveQueue::new(QueuePolicy::QueueAll, 2);
+        queue.force_push(1);
+        queue.force_push(2);
+        assert!(queue.push(3, 0));
+        assert!(queue.push(4, 0));
+        assert!(!queue.push(5, 0));
+        assert_eq!(drain(&queue), vec![1, 2, 3, 4]);
+
+        let queue = MoveQueue::new(QueuePolicy::DropWhileBusy, 2);
+        queue.force_push(1);
+        assert!(queue.push(2, 0));
+        assert_eq!(queue.pop_until(Instant::now()), Some(1));
+        assert!(!queue.push(3, 0));
+        assert_eq!(queue.pop_until(Instant::now()), Some(2));
+        assert!(!queue.push(4, 0));
+        queue.done();
+        queue.force_push(5);
+        assert_eq!(queue.pop_until(Instant::now()), Some(5));
+        assert!(queue.push(6, 0));
+        queue.done();
+    }
+
+    #[test]
+    fn closed_queues_take_nothing() {
+        let queue = MoveQueue::new(QueuePolicy::QueueAll, 3);
+        queue.push(1, 0);
+        queue.close();
+        assert!(queue.is_closed());
+        assert!(!queue.push(2, 0));
+        assert_eq!(queue.pop_until(Instant::now()), None);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::canvas::{mxcfb_rect, Canvas, Point2, Refresh};
+use crate::config::Action;
+use crate::game_screen::{self, GameScreen};
+use crate::puzzles::{Pack, PuzzleProgress};
+use crate::screen::{Context, Input, Screen, Transition};
+use anyhow::Result;
+
+const TITLE_FONT_SIZE: f32 = 100.0;
+const SUMMARY_FONT_SIZE: f32 = 50.0;
+/// Baseline of the pack name and progress below the title
+const SUMMARY_Y: i32 = 280;
+const ROW_FONT_SIZE: f32 = 50.0;
+const ROW_HEIGHT: u32 = 130;
+/// Top of the first row
+const ROWS_TOP: u32 = 340;
+const ERROR_FONT_SIZE: f32 = 35.0;
+const ERROR_MAX_CHARS_PER_LINE: usize = 60;
+const BUTTON_FONT_SIZE: f32 = 60.0;
+const BUTTON_WIDTH: u32 = 350;
+const BUTTON_HEIGHT: u32 = 110;
+const BUTTON_GAP: u32 = 50;
+/// Space between the bottom of the buttons and the bottom of the screen
+const BUTTONS_BOTTOM_MARGIN: u32 = 50;
+
+/// The puzzles of the pack, page by page. Tapping one plays it.
+pub struct PuzzleSelect {
+    pack: Result<Pack>,
+    page: usize,
+    /// Hitboxes of the puzzles on the page with their index in the pack
+    rows: Vec<(mxcfb_rect, usize)>,
+    previous_button: mxcfb_rect,
+    back_button: mxcfb_rect,
+    next_button: mxcfb_rect,
+}
+
+impl PuzzleSelect {
+    pub fn new() -> Self {
+        let pack = Pack::load();
+        if let Err(e) = &pack {
+            error!("Failed to load the puzzles: {:?}", e);
+        }
+        Self {
+            pack,
+            page: 0,
+            rows: Vec::new(),
+            previous_button: mxcfb_rect::invalid(),
+            back_button: mxcfb_rect::invalid(),
+            next_button: mxcfb_rect::invalid(),
+        }
+    }
+
+    /// Rows that fit between the summary and the buttons.
+    fn rows_per_page(canvas: &Canvas) -> usize {
+        let bottom = canvas.height() - BUTTONS_BOTTOM_MARGIN - BUTTON_HEIGHT - BUTTON_GAP;
+        ((bottom - ROWS_TOP) / ROW_HEIGHT) as usize
+    }
+
+    fn pages(&self, canvas: &Canvas) -> usize {
+        let puzzles = self.pack.as_ref().map_or(0, |pack| pack.puzzles.len());
+        puzzles.div_ceil(Self::rows_per_page(canvas)).max(1)
+    }
+}
+
+impl Screen for PuzzleSelect {
+    fn draw(&mut self, ctx: &mut Context) -> Result<()> {
+        let canvas = &mut ctx.canvas;
+        canvas.clear();
+        canvas.draw_text(
+            Point2 {
+                x: None,
+                y: Some(175),
+            },
+            "Puzzles",
+            TITLE_FONT_SIZE,
+        );
+        self.page = self.page.min(self.pages(canvas) - 1);
+        match &self.pack {
+            Ok(pack) => self.rows = draw_puzzles(canvas, pack, self.page),
+            Err(e) => {
+                self.rows.clear();
+                canvas.draw_multi_line_text(
+                    None,
+                    ROWS_TOP as i32,
+                    &format!("{:#}", e),
+                    ERROR_MAX_CHARS_PER_LINE,
+                    10,
+                    ERROR_FONT_SIZE,
+                    1.0,
+                );
+            }
+        }
+
+        // Previous and next only where there is another page
+        let buttons_top = canvas.height() - BUTTONS_BOTTOM_MARGIN - BUTTON_HEIGHT;
+        let center_x = canvas.width() / 2;
+        let button = |left| mxcfb_rect {
+            left,
+            top: buttons_top,
+            width: BUTTON_WIDTH,
+            height: BUTTON_HEIGHT,
+        };
+        self.previous_button = mxcfb_rect::invalid();
+        self.next_button = mxcfb_rect::invalid();
+        if self.page > 0 {
+            self.previous_button = canvas.draw_fixed_button(
+                button(center_x - BUTTON_WIDTH * 3 / 2 - BUTTON_GAP),
+                "Previous",
+                BUTTON_FONT_SIZE,
+            );
+        }
+        self.back_button = canvas.draw_fixed_button(
+            button(center_x - BUTTON_WIDTH / 2),
+            "Back",
+            BUTTON_FONT_SIZE,
+        );
+        if self.page + 1 < self.pages(canvas) {
+            self.next_button = canvas.draw_fixed_button(
+                button(center_x + BUTTON_WIDTH / 2 + BUTTON_GAP),
+                "Next",
+                BUTTON_FONT_SIZE,
+            );
+        }
+        canvas.update_full(Refresh::Quality);
+        Ok(())
+    }
+
+    fn handle_input(&mut self, ctx: &mut Context, input: Input) -> Result<Transition> {
+        Ok(match input {
+            Input::Tap(pos) if Canvas::is_hitting(pos, self.back_button) => Transition::Pop,
+            Input::Tap(pos) if Canvas::is_hitting(pos, self.previous_button) => {
+                self.page -= 1;
+                self.draw(ctx)?;
+                Transition::Stay
+            }
+            Input::Tap(pos) if Canvas::is_hitting(pos, self.next_button) => {
+                self.page += 1;
+                self.draw(ctx)?;
+                Transition::Stay
+            }
+            Input::Tap(pos) => {
+                let index = self
+                    .rows
+                    .iter()
+                    .find(|(area, _)| Canvas::is_hitting(pos, *area))
+                    .map(|(_, index)| *index);
+                match (index, &self.pack) {
+                    (Some(index), Ok(pack)) => {
+                        Transition::Push(Box::new(GameScreen::puzzle(pack.clone(), index)?))
+                    }
+                    _ => Transition::Stay,
+                }
+            }
+            Input::Action(Action::Menu) => Transition::Pop,
+            Input::Action(action) => game_screen::handle_action(ctx, action),
+            Input::Config(_) => {
+                ctx.canvas.set_orientation(ctx.config.orientation);
+                self.draw(ctx)?;
+                Transition::Stay
+            }
+            _ => Transition::Stay,
+        })
+    }
+}
+
+/// Draws the progress of the pack and the puzzles on the page.
+/// Returns their hitboxes with their index in the pack.
+fn draw_puzzles(canvas: &mut Canvas, pack: &Pack, page: usize) -> Vec<(mxcfb_rect, usize)> {
+    let progress = PuzzleProgress::load().unwrap_or_else(|e| {
+        error!("Failed to load the puzzle progress: {:?}", e);
+        PuzzleProgress::default()
+    });
+    let solved = pack
+        .puzzles
+        .iter()
+        .filter(|puzzle| progress.get(&pack.key(puzzle)).best_moves.is_some())
+        .count();
+    canvas.draw_text(
+        Point2 {
+            x: None,
+            y: Some(SUMMARY_Y),
+        },
+        &format!("{}: {} of {} solved", pack.name, solved, pack.puzzles.len()),
+        SUMMARY_FONT_SIZE,
+    );
+
+    let rows_per_page = PuzzleSelect::rows_per_page(canvas);
+    let mut rows = Vec::new();
+    for (row, index) in (page * rows_per_page..pack.puzzles.len())
+        .take(rows_per_page)
+        .enumerate()
+    {
+        let puzzle = &pack.puzzles[index];
+        let progress = progress.get(&pack.key(puzzle));
+        let label = match (progress.best_moves, progress.attempts) {
+            (Some(moves), _) => format!("{}. {} (solved in {})", index + 1, puzzle.name, moves),
+            (None, 0) => format!("{}. {}", index + 1, puzzle.name),
+            (None, 1) => format!("{}. {} (1 try)", index + 1, puzzle.name),
+            (None, attempts) => {
+                format!("{}. {} ({} tries)", index + 1, puzzle.name, attempts)
+            }
+        };
+        let top = ROWS_TOP + row as u32 * ROW_HEIGHT;
+        let area = canvas.draw_box_button(top as i32, ROW_HEIGHT, &label, ROW_FONT_SIZE);
+        rows.push((area, index));
+    }
+    rows
+}
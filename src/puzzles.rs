@@ -0,0 +1,308 @@
+use crate::board_code::Board;
+use crate::config::value_enum;
+use crate::game::{Game, Mode};
+use crate::rules::{Rules, DEFAULT_BASE};
+use crate::stats;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+
+/// Pack that is played unless there is a PACK_FILE.
+const BUILTIN_PACK: &str = include_str!("../res/puzzles.toml");
+/// Puzzle pack of the player, in the data directory.
+const PACK_FILE: &str = "puzzles.toml";
+const PROGRESS_FILE: &str = "puzzle-progress.json";
+
+/// What it takes to solve a puzzle.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Goal {
+    /// A tile of at least this value
+    Reach(u32),
+    /// At least this score, including the score of the board code
+    Score(u32),
+    /// All tiles merged into one
+    OneTile,
+}
+
+/// How a puzzle ended.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Outcome {
+    Solved,
+    /// The moves are used up
+    OutOfMoves,
+    /// No move is possible anymore
+    Stuck,
+}
+
+/// A starting board with a goal. Tiles only spawn as listed,
+/// at random but always at the same place for the same moves.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Puzzle {
+    pub name: String,
+    #[serde(default = "classic", with = "value_enum")]
+    pub mode: Mode,
+    /// Only used for custom-base puzzles
+    pub base: Option<u32>,
+    /// Board code as logged at game over
+    pub board: String,
+    /// Values of the tiles spawned after each move
+    #[serde(default)]
+    pub spawns: Vec<u32>,
+    /// Where the tiles spawn
+    #[serde(default)]
+    pub seed: u64,
+    pub goal: Goal,
+    /// Moves it has to be solved in
+    pub moves: Option<u32>,
+}
+
+fn classic() -> Mode {
+    Mode::Classic
+}
+
+impl Puzzle {
+    pub fn rules(&self) -> Rules {
+        self.mode.rules(self.base.unwrap_or(DEFAULT_BASE))
+    }
+
+    /// A game on the starting board.
+    pub fn game(&self) -> Result<Game> {
+        let rules = self.rules();
+        let board = Board::decode(&self.board)?;
+        let mut game = Game::with_cells(
+            self.mode,
+            rules,
+            board.size,
+            self.seed,
+            board.cells(rules)?,
+            board.score,
+        );
+        game.spawns = Some(self.spawns.iter().copied().collect());
+        Ok(game)
+    }
+
+    /// Checks that the puzzle can be played.
+    fn validate(&self) -> Result<()> {
+        self.game()?;
+        let rules = self.rules();
+        if let Some(tile) = self.spawns.iter().find(|tile| rules.rank(**tile).is_none()) {
+            bail!("{} can't spawn with {:?}", tile, rules);
+        }
+        if let Goal::Reach(tile) = self.goal {
+            ensure!(
+                rules.rank(tile).is_some(),
+                "{} can't be reached with {:?}",
+                tile,
+                rules
+            );
+        }
+        Ok(())
+    }
+
+    /// How the game ended. None while it can go on.
+    pub fn outcome(&self, game: &Game) -> Option<Outcome> {
+        let solved = match self.goal {
+            Goal::Reach(tile) => game.max_tile() >= tile,
+            Goal::Score(score) => game.score >= score,
+            Goal::OneTile => game.cells().iter().filter_map(|cell| cell.tile()).count() == 1,
+        };
+        if solved {
+            Some(Outcome::Solved)
+        } else if self.moves_left(game) == Some(0) {
+            Some(Outcome::OutOfMoves)
+        } else if !game.can_move() {
+            Some(Outcome::Stuck)
+        } else {
+            None
+        }
+    }
+
+    pub fn moves_left(&self, game: &Game) -> Option<u32> {
+        self.moves.map(|moves| moves.saturating_sub(game.moves))
+    }
+
+    /// The goal as shown above the board, e.g. "Reach 512 in 12 moves".
+    pub fn status(&self, game: &Game) -> String {
+        let goal = match self.goal {
+            Goal::Reach(tile) => format!("Reach {}", tile),
+            Goal::Score(score) => format!("Score {}", score),
+            Goal::OneTile => "Merge into one tile".to_owned(),
+        };
+        match self.moves_left(game) {
+            Some(1) => format!("{} in 1 move", goal),
+            Some(moves) => format!("{} in {} moves", goal, moves),
+            None => goal,
+        }
+    }
+}
+
+/// Puzzles meant to be solved in order.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pack {
+    pub name: String,
+    pub puzzles: Vec<Puzzle>,
+}
+
+impl Pack {
+    /// The pack of the player, or the built-in one if there is none.
+    pub fn load() -> Result<Self> {
+        let path = stats::data_dir().join(PACK_FILE);
+        let (toml, source) = match fs::read_to_string(&path) {
+            Ok(toml) => (toml, format!("{:?}", path)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                (BUILTIN_PACK.to_owned(), "built-in pack".to_owned())
+            }
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+        };
+        let pack: Self =
+            toml::from_str(&toml).with_context(|| format!("Invalid puzzles in {}", source))?;
+        for puzzle in &pack.puzzles {
+            puzzle
+                .validate()
+                .with_context(|| format!("Invalid puzzle {:?} in {}", puzzle.name, source))?;
+        }
+        Ok(pack)
+    }
+
+    /// Key of the puzzle in PROGRESS_FILE.
+    pub fn key(&self, puzzle: &Puzzle) -> String {
+        format!("{}/{}", self.name, puzzle.name)
+    }
+}
+
+/// How a single puzzle went so far.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Progress {
+    pub attempts: u32,
+    /// None until solved
+    pub best_moves: Option<u32>,
+}
+
+/// Progress of all puzzles played, stored in PROGRESS_FILE.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PuzzleProgress {
+    /// See Pack::key() => Progress
+    puzzles: BTreeMap<String, Progress>,
+}
+
+impl PuzzleProgress {
+    pub fn load() -> Result<Self> {
+        let path = stats::data_dir().join(PROGRESS_FILE);
+        match fs::read_to_string(&path) {
+            Ok(json) => {
+                serde_json::from_str(&json).with_context(|| format!("Failed to parse {:?}", path))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {:?}", path)),
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let dir = stats::data_dir();
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {:?}", dir))?;
+        let path = dir.join(PROGRESS_FILE);
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    pub fn get(&self, key: &str) -> Progress {
+        self.puzzles.get(key).cloned().unwrap_or_default()
+    }
+
+    /// Counts an attempt, solved in `moves` if given.
+    pub fn record(&mut self, key: String, moves: Option<u32>) {
+        let progress = self.puzzles.entry(key).or_default();
+        progress.attempts += 1;
+        if let Some(moves) = moves {
+            progress.best_moves = Some(progress.best_moves.map_or(moves, |best| best.min(moves)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Direction;
+
+    fn puzzle(board: &str, goal: Goal, moves: Option<u32>) -> Puzzle {
+        Puzzle {
+            name: "Test".to_owned(),
+            mode: Mode::Classic,
+            base: None,
+            board: board.to_owned(),
+            spawns: Vec::new(),
+            seed: 0,
+            goal,
+            moves,
+        }
+    }
+
+    #[test]
+    fn parses_the_builtin_pack() {
+        let pack: Pack = toml::from_str(BUILTIN_PACK).unwrap();
+        assert!(!pack.puzzles.is_empty());
+        for puzzle in &pack.puzzles {
+            puzzle.validate().unwrap();
+        }
+        assert_eq!(pack.puzzles[0].mode, Mode::Classic);
+        assert_eq!(pack.puzzles[0].goal, Goal::Reach(16));
+        assert_eq!(pack.puzzles[1].goal, Goal::OneTile);
+    }
+
+    #[test]
+    fn rejects_puzzles_that_cant_be_played() {
+        assert!(puzzle("4-1123000000000000-0", Goal::Reach(24), None)
+            .validate()
+            .is_err());
+        assert!(puzzle("4-1123-0", Goal::Reach(16), None)
+            .validate()
+            .is_err());
+        let mut puzzle = puzzle("4-1123000000000000-0", Goal::Reach(16), None);
+        puzzle.spawns = vec![3];
+        assert!(puzzle.validate().is_err());
+    }
+
+    #[test]
+    fn is_solved_once_the_goal_is_reached() {
+        let puzzle = puzzle("4-1123000000000000-0", Goal::Reach(16), Some(3));
+        let mut game = puzzle.game().unwrap();
+        assert!(game.play(Direction::Left));
+        assert!(game.play(Direction::Left));
+        assert_eq!(puzzle.outcome(&game), None);
+        assert_eq!(puzzle.status(&game), "Reach 16 in 1 move");
+        assert!(game.play(Direction::Left));
+        assert_eq!(puzzle.outcome(&game), Some(Outcome::Solved));
+    }
+
+    #[test]
+    fn ends_when_the_moves_are_used_up() {
+        let puzzle = puzzle("4-1123000000000000-0", Goal::OneTile, Some(1));
+        let mut game = puzzle.game().unwrap();
+        assert_eq!(puzzle.status(&game), "Merge into one tile in 1 move");
+        assert!(game.play(Direction::Left));
+        assert_eq!(puzzle.outcome(&game), Some(Outcome::OutOfMoves));
+    }
+
+    #[test]
+    fn ends_when_no_move_is_left() {
+        let puzzle = puzzle("3-121212121-0", Goal::Score(100), None);
+        let game = puzzle.game().unwrap();
+        assert_eq!(puzzle.outcome(&game), Some(Outcome::Stuck));
+        assert_eq!(puzzle.status(&game), "Score 100");
+    }
+
+    #[test]
+    fn keeps_the_fewest_moves() {
+        let mut progress = PuzzleProgress::default();
+        progress.record("Pack/Puzzle".to_owned(), None);
+        progress.record("Pack/Puzzle".to_owned(), Some(5));
+        progress.record("Pack/Puzzle".to_owned(), Some(7));
+        let progress = progress.get("Pack/Puzzle");
+        assert_eq!(progress.attempts, 3);
+        assert_eq!(progress.best_moves, Some(5));
+    }
+}
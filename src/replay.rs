@@ -0,0 +1,132 @@
+use crate::board_view::BoardView;
+use crate::canvas::Canvas;
+use crate::cli::ReplayArgs;
+use crate::config::Config;
+use crate::game::{Cell, Game};
+use crate::layout::Layout;
+use crate::stats::GameRecord;
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+/// Shows a recorded game move by move.
+pub fn run(args: &ReplayArgs, mut config: Config) -> Result<()> {
+    let records = load_records(&args.file)?;
+    let index = args.game.unwrap_or_else(|| records.len().saturating_sub(1));
+    let record = records
+        .get(index)
+        .ok_or_else(|| anyhow!("{:?} contains {} games", args.file, records.len()))?;
+    ensure!(
+        record.moves == 0 || !record.move_list.is_empty(),
+        "Game {} was recorded without its moves",
+        index
+    );
+
+    let mut cells = vec![Cell::Empty; record.board_size * record.board_size];
+    for (x, y) in &record.walls {
+        ensure!(
+            *x < record.board_size && *y < record.board_size,
+            "Wall ({}, {}) is outside of the board",
+            x,
+            y
+        );
+        cells[y * record.board_size + x] = Cell::Wall;
+    }
+    for tile in &record.start_tiles {
+        ensure!(
+            tile.x < record.board_size && tile.y < record.board_size,
+            "Start tile {:?} is outside of the board",
+            tile
+        );
+        cells[tile.y * record.board_size + tile.x] = Cell::Tile(tile.value);
+    }
+    let mut game = Game::with_cells(
+        record.mode,
+        record.rules(),
+        record.board_size,
+        record.seed,
+        cells,
+        0,
+    );
+
+    args.display.apply(&mut config);
+    let mut canvas = Canvas::new(config.orientation);
+    let mut view = BoardView::new(
+        Layout::new(config.orientation, config.handedness, game.size),
+        &config,
+        game.rules,
+    );
+    view.draw_game(&mut canvas, &game)?;
+    info!(
+        "Replaying game {} with {} moves",
+        index,
+        record.move_list.len()
+    );
+
+    let delay = Duration::from_millis(args.delay_ms);
+    for (i, move_record) in record.move_list.iter().enumerate() {
+        thread::sleep(delay);
+        let last = game.cells().to_vec();
+        ensure!(
+            game.play(move_record.direction),
+            "Move {} ({:?}) doesn't change the board. The recording seems to be broken.",
+            i + 1,
+            move_record.direction
+        );
+        let markers = view.draw_cells(&mut canvas, Some(&last), game.cells())?;
+        view.draw_score(&mut canvas, &game);
+        if let Some(tile) = move_record.spawned {
+            ensure!(
+                tile.x < game.size && tile.y < game.size && game.get(tile.x, tile.y) == Cell::Empty,
+                "Tile {:?} of move {} can't be spawned",
+                tile,
+                i + 1
+            );
+            canvas.wait_for_updates(&markers);
+            let last = game.cells().to_vec();
+            game.place_tile(tile);
+            let markers = view.animate_spawned_tiles(&mut canvas, &last, game.cells())?;
+            if let Some(tile) = move_record.removed {
+                ensure!(
+                    game.get(tile.x, tile.y) == Cell::Tile(tile.value),
+                    "Tile {:?} of move {} can't be removed",
+                    tile,
+                    i + 1
+                );
+                canvas.wait_for_updates(&markers);
+                let last = game.cells().to_vec();
+                game.remove_tile(tile);
+                view.draw_cells(&mut canvas, Some(&last), game.cells())?;
+            }
+        }
+    }
+    canvas.wait_for_all_updates();
+    info!(
+        "Replay finished with a score of {} (recorded {})",
+        game.score, record.score
+    );
+    Ok(())
+}
+
+/// Reads games from a JSON export, a single exported game or
+/// a file with one game per line (like games.jsonl).
+fn load_records(path: &Path) -> Result<Vec<GameRecord>> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+    if let Ok(records) = serde_json::from_str(&content) {
+        return Ok(records);
+    }
+    if let Ok(record) = serde_json::from_str(&content) {
+        return Ok(vec![record]);
+    }
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("Line {} of {:?} is no recorded game", i + 1, path))
+        })
+        .collect()
+}
@@ -0,0 +1,216 @@
+use crate::game::MAX_BOARD_SIZE;
+use rand::Rng;
+
+/// Probability of a spawned tile being the second smallest one
+/// (a 4 in classic games) instead of the smallest.
+const PROBA_SECOND_SMALLEST: f32 = 0.2;
+/// Smallest tile that counts as a won game, whatever the rules.
+/// Makes it 2048 for classic games.
+const WINNING_MIN: u32 = 2048;
+pub const DEFAULT_BASE: u32 = 4;
+pub const MIN_BASE: u32 = 2;
+/// More tiles than fit into a line could never merge.
+pub const MAX_BASE: u32 = MAX_BOARD_SIZE as u32;
+
+/// Which tiles merge and which tiles appear. Tiles are told apart
+/// by their rank: 1 for the smallest tile and one more for each
+/// merge it takes to build a tile.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Rules {
+    /// `base` equal tiles in a row merge into one with `base` times
+    /// their value. The tiles are the powers of the base: 2, 4, 8, ..
+    /// for base 2 and 3, 9, 27, .. for base 3.
+    Powers { base: u32 },
+    /// Neighbors in the Fibonacci sequence merge into their
+    /// sum: 1, 2, 3, 5, 8, ..
+    Fibonacci,
+}
+
+impl Rules {
+    /// Number of tiles taking part in a merge.
+    pub fn group_size(self) -> usize {
+        match self {
+            Rules::Powers { base } => base as usize,
+            Rules::Fibonacci => 2,
+        }
+    }
+
+    /// The tile that the group merges into, if it does. Groups of
+    /// another size than group_size() never merge.
+    pub fn merge(self, group: &[u32]) -> Option<u32> {
+        if group.len() != self.group_size() {
+            return None;
+        }
+        match self {
+            Rules::Powers { base } => {
+                let first = group[0];
+                let equal = first != 0 && group.iter().all(|tile| *tile == first);
+                equal.then(|| first.checked_mul(base)).flatten()
+            }
+            Rules::Fibonacci => {
+                let (a, b) = (group[0], group[1]);
+                let (rank_a, rank_b) = (self.rank(a)?, self.rank(b)?);
+                // The sequence starts with 1, 1
+                let neighbors = rank_a.abs_diff(rank_b) == 1 || (a == 1 && b == 1);
+                neighbors.then(|| a.checked_add(b)).flatten()
+            }
+        }
+    }
+
+    /// Merges the tiles of a row or column, beginning with the tile
+    /// all of them are moved towards. Each tile merges at most once.
+    /// Returns the remaining tiles and the points gained.
+    pub fn merge_line(self, tiles: &[u32]) -> (Vec<u32>, u32) {
+        let group_size = self.group_size();
+        let mut merged = Vec::with_capacity(tiles.len());
+        let mut points = 0;
+        let mut i = 0;
+        while i < tiles.len() {
+            let group = &tiles[i..(i + group_size).min(tiles.len())];
+            match self.merge(group) {
+                Some(tile) => {
+                    points += tile;
+                    merged.push(tile);
+                    i += group_size;
+                }
+                None => {
+                    merged.push(tiles[i]);
+                    i += 1;
+                }
+            }
+        }
+        (merged, points)
+    }
+
+    /// Value of a new tile: mostly the smallest tile and sometimes
+    /// the one after it.
+    pub fn spawn_value(self, rng: &mut impl Rng) -> u32 {
+        let rank = if rng.gen::<f32>() < PROBA_SECOND_SMALLEST {
+            2
+        } else {
+            1
+        };
+        self.value(rank).expect("Rules without small tiles")
+    }
+
+    /// Tile value that counts as a won game.
+    pub fn winning_tile(self) -> u32 {
+        self.first_tile_from(WINNING_MIN)
+    }
+
+    /// The smallest tile that is at least `min`. u32::MAX if
+    /// there is none.
+    pub fn first_tile_from(self, min: u32) -> u32 {
+        (1..)
+            .map_while(|rank| self.value(rank))
+            .find(|tile| *tile >= min)
+            .unwrap_or(u32::MAX)
+    }
+
+    /// Rank of the tile. None for values that can't be built.
+    pub fn rank(self, value: u32) -> Option<u32> {
+        match self {
+            Rules::Powers { base } => {
+                let (mut rest, mut rank) = (value, 0);
+                while rest > 1 && rest % base == 0 {
+                    rest /= base;
+                    rank += 1;
+                }
+                (rest == 1 && rank > 0).then_some(rank)
+            }
+            Rules::Fibonacci => (1..)
+                .map(|rank| (rank, self.value(rank)))
+                .find(|(_, tile)| tile.is_none_or(|tile| tile >= value))
+                .filter(|(_, tile)| *tile == Some(value))
+                .map(|(rank, _)| rank),
+        }
+    }
+
+    /// Value of the tile with the given rank (starting at 1). None if
+    /// it doesn't fit into a u32.
+    pub fn value(self, rank: u32) -> Option<u32> {
+        if rank == 0 {
+            return None;
+        }
+        match self {
+            Rules::Powers { base } => base.checked_pow(rank),
+            Rules::Fibonacci => {
+                let (mut value, mut next) = (1u32, 2u32);
+                for _ in 1..rank {
+                    (value, next) = (next, value.checked_add(next)?);
+                }
+                Some(value)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_pairs_of_powers_of_two() {
+        let rules = Rules::Powers { base: 2 };
+        assert_eq!(rules.merge_line(&[2, 2, 4, 4]), (vec![4, 8], 12));
+        // Merged tiles don't merge again in the same move
+        assert_eq!(rules.merge_line(&[2, 2, 4]), (vec![4, 4], 4));
+        assert_eq!(rules.merge_line(&[2, 2, 2]), (vec![4, 2], 4));
+        assert_eq!(rules.merge_line(&[2, 4, 2]), (vec![2, 4, 2], 0));
+        assert_eq!(rules.merge_line(&[]), (vec![], 0));
+    }
+
+    #[test]
+    fn merges_groups_of_the_base() {
+        let rules = Rules::Powers { base: 3 };
+        assert_eq!(rules.merge_line(&[3, 3, 3, 3]), (vec![9, 3], 9));
+        assert_eq!(rules.merge_line(&[3, 3]), (vec![3, 3], 0));
+        assert_eq!(rules.merge_line(&[9, 3, 3, 3]), (vec![9, 9], 9));
+        assert_eq!(rules.merge_line(&[3, 3, 9]), (vec![3, 3, 9], 0));
+        let rules = Rules::Powers { base: 4 };
+        assert_eq!(rules.merge_line(&[4, 4, 4, 4, 4]), (vec![16, 4], 16));
+        assert_eq!(rules.merge(&[4, 4, 4]), None);
+    }
+
+    #[test]
+    fn powers_stop_at_the_largest_value() {
+        let rules = Rules::Powers { base: 3 };
+        let largest = 3u32.pow(20);
+        assert_eq!(rules.merge(&[largest; 3]), None);
+        assert_eq!(rules.value(21), None);
+        assert_eq!(rules.winning_tile(), 2187);
+    }
+
+    #[test]
+    fn merges_fibonacci_neighbors() {
+        let rules = Rules::Fibonacci;
+        assert_eq!(rules.merge_line(&[1, 1, 2]), (vec![2, 2], 2));
+        assert_eq!(rules.merge_line(&[3, 2, 5, 8]), (vec![5, 13], 18));
+        assert_eq!(rules.merge_line(&[2, 2, 1, 3]), (vec![2, 3, 3], 3));
+        assert_eq!(rules.merge_line(&[1, 3]), (vec![1, 3], 0));
+        assert_eq!(rules.merge(&[4, 1]), None);
+    }
+
+    #[test]
+    fn fibonacci_merges_stop_at_the_largest_value() {
+        let rules = Rules::Fibonacci;
+        assert_eq!(
+            rules.merge(&[1_134_903_170, 1_836_311_903]),
+            Some(2_971_215_073)
+        );
+        assert_eq!(rules.merge(&[1_836_311_903, 2_971_215_073]), None);
+    }
+
+    #[test]
+    fn ranks_tiles() {
+        let rules = Rules::Fibonacci;
+        let ranks: Vec<Option<u32>> = [1, 2, 3, 4, 5, 8].iter().map(|v| rules.rank(*v)).collect();
+        assert_eq!(ranks, [Some(1), Some(2), Some(3), None, Some(4), Some(5)]);
+        assert_eq!(Rules::Powers { base: 2 }.rank(2048), Some(11));
+        assert_eq!(Rules::Powers { base: 2 }.rank(6), None);
+        for rules in [Rules::Powers { base: 2 }, Rules::Fibonacci] {
+            assert_eq!(rules.rank(0), None);
+            assert!((1..20).all(|rank| rules.rank(rules.value(rank).unwrap()) == Some(rank)));
+        }
+    }
+}
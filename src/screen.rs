@@ -0,0 +1,352 @@
+use crate::board_view::BoardView;
+use crate::canvas::{Canvas, Point2};
+use crate::config::{Action, Config};
+use crate::swipe::Direction as SwipeDirection;
+use anyhow::Result;
+use libremarkable::input::gpio::PhysicalButton;
+
+/// Input for the render thread
+#[derive(Debug, Copy, Clone)]
+pub enum Input {
+    /// As detected on the touchscreen (see GameScreen), with the
+    /// half it happened in (see RegionSwipeTracker::halves())
+    Swipe(SwipeDirection, usize),
+    /// As reported by the touchscreen. Screens get it in the
+    /// coordinates of the canvas (see Orientation::to_screen()).
+    Tap(Point2<u16>),
+    /// A hardware button was pressed. Only screens that use the
+    /// buttons themselves get it (see Screen::uses_buttons()).
+    Button(PhysicalButton),
+    /// A hardware button as mapped by the keymap
+    Action(Action),
+    /// The config file changed. Already applied to the Context.
+    Config(Config),
+    /// Sent every timer::TICK_INTERVAL for screens showing a clock
+    Tick,
+}
+
+/// What the navigator does after a screen handled some input.
+pub enum Transition {
+    Stay,
+    /// Shows the screen on top of the current one
+    Push(Box<dyn Screen>),
+    /// Back to the screen below. Quits if there is none.
+    Pop,
+    /// Swaps the current screen for another one
+    Replace(Box<dyn Screen>),
+    /// Removes all screens and starts over with this one
+    Reset(Box<dyn Screen>),
+    Quit,
+}
+
+/// Everything the screens share.
+pub struct Context<'a> {
+    pub canvas: Canvas<'a>,
+    pub config: Config,
+    /// Kept across games since rendering its tiles takes a while
+    pub view: BoardView,
+}
+
+/// Something that fills the whole screen and handles the input
+/// while it is on top of the navigator.
+pub trait Screen {
+    /// Called whenever the screen gets on top, either by being
+    /// pushed or by the screen above it being popped. The canvas
+    /// is already turned to the configured orientation.
+    fn on_enter(&mut self, ctx: &mut Context) -> Result<()> {
+        self.draw(ctx)
+    }
+
+    /// Draws the whole screen onto a cleared canvas.
+    fn draw(&mut self, ctx: &mut Context) -> Result<()>;
+
+    fn handle_input(&mut self, ctx: &mut Context, input: Input) -> Result<Transition>;
+
+    /// Called once the screen is removed from the navigator.
+    fn on_exit(&mut self, _ctx: &mut Context) {}
+
+    /// Whether the hardware buttons should arrive as Input::Button
+    /// instead of as the action they are mapped to.
+    fn uses_buttons(&self) -> bool {
+        false
+    }
+
+    /// Whether swipes on the two halves of the touchscreen are
+    /// separate moves (see MoveQueue::set_split_sources()).
+    fn splits_swipes(&self) -> bool {
+        false
+    }
+}
+
+/// Stack of screens of which only the top one is visible.
+pub struct Navigator {
+    stack: Vec<Box<dyn Screen>>,
+}
+
+impl Navigator {
+    /// Starts with the given screens (bottom first) and enters the top one.
+    pub fn new(ctx: &mut Context, stack: Vec<Box<dyn Screen>>) -> Result<Self> {
+        ensure!(!stack.is_empty(), "There has to be a screen to show");
+        let mut navigator = Self { stack };
+        navigator.enter(ctx)?;
+        Ok(navigator)
+    }
+
+    /// Passes the input on to the top screen and does the transition
+    /// it asks for. Returns false once there is nothing left to show.
+    pub fn handle_input(&mut self, ctx: &mut Context, input: Input) -> Result<bool> {
+        let screen = match self.stack.last_mut() {
+            Some(screen) => screen,
+            None => return Ok(false),
+        };
+        let input = match input {
+            Input::Tap(pos) => Input::Tap(ctx.canvas.orientation().to_screen(pos)),
+            Input::Button(button) if !screen.uses_buttons() => {
+                let action = ctx.config.keys.action(button);
+                debug!("{:?} => {:?}", button, action);
+                Input::Action(action)
+            }
+            Input::Config(config) => {
+                ctx.config = config;
+                input
+            }
+            _ => input,
+        };
+        let transition = screen.handle_input(ctx, input)?;
+        self.apply(ctx, transition)
+    }
+
+    /// See Screen::splits_swipes() of the top screen.
+    pub fn splits_swipes(&self) -> bool {
+        self.stack
+            .last()
+            .is_some_and(|screen| screen.splits_swipes())
+    }
+
+    fn apply(&mut self, ctx: &mut Context, transition: Transition) -> Result<bool> {
+        match transition {
+            Transition::Stay => return Ok(true),
+            Transition::Push(screen) => self.stack.push(screen),
+            Transition::Pop => self.pop(ctx),
+            Transition::Replace(screen) => {
+                self.pop(ctx);
+                self.stack.push(screen);
+            }
+            Transition::Reset(screen) => {
+                self.clear(ctx);
+                self.stack.push(screen);
+            }
+            Transition::Quit => self.clear(ctx),
+        }
+        if self.stack.is_empty() {
+            return Ok(false);
+        }
+        self.enter(ctx)?;
+        Ok(true)
+    }
+
+    fn enter(&mut self, ctx: &mut Context) -> Result<()> {
+        ctx.canvas.set_orientation(ctx.config.orientation);
+        if let Some(screen) = self.stack.last_mut() {
+            screen.on_enter(ctx)?;
+        }
+        Ok(())
+    }
+
+    fn pop(&mut self, ctx: &mut Context) {
+        if let Some(mut screen) = self.stack.pop() {
+            screen.on_exit(ctx);
+        }
+    }
+
+    fn clear(&mut self, ctx: &mut Context) {
+        while !self.stack.is_empty() {
+            self.pop(ctx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::Orientation;
+    use crate::game::DEFAULT_BOARD_SIZE;
+    use crate::layout::Layout;
+    use crate::rules::Rules;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    /// Logs what happens to it and answers the next input with
+    /// the given transition.
+    struct Stub {
+        name: &'static str,
+        log: Log,
+        next: Option<Transition>,
+    }
+
+    impl Stub {
+        fn new(name: &'static str, log: &Log) -> Box<Self> {
+            Box::new(Self {
+                name,
+                log: log.clone(),
+                next: None,
+            })
+        }
+
+        fn then(mut self: Box<Self>, transition: Transition) -> Box<Self> {
+            self.next = Some(transition);
+            self
+        }
+
+        fn record(&self, event: &str) {
+            self.log
+                .borrow_mut()
+                .push(format!("{} {}", event, self.name));
+        }
+    }
+
+    impl Screen for Stub {
+        fn on_enter(&mut self, _ctx: &mut Context) -> Result<()> {
+            self.record("enter");
+            Ok(())
+        }
+
+        fn draw(&mut self, _ctx: &mut Context) -> Result<()> {
+            Ok(())
+        }
+
+        fn handle_input(&mut self, _ctx: &mut Context, _input: Input) -> Result<Transition> {
+            Ok(self.next.take().unwrap_or(Transition::Stay))
+        }
+
+        fn on_exit(&mut self, _ctx: &mut Context) {
+            self.record("exit");
+        }
+    }
+
+    fn context() -> Context<'static> {
+        let config = Config::default();
+        Context {
+            canvas: Canvas::headless(Orientation::Portrait).unwrap(),
+            view: BoardView::new(
+                Layout::new(config.orientation, config.handedness, DEFAULT_BOARD_SIZE),
+                &config,
+                Rules::Powers { base: 2 },
+            ),
+            config,
+        }
+    }
+
+    /// Events since the last call.
+    fn events(log: &Log) -> Vec<String> {
+        log.borrow_mut().drain(..).collect()
+    }
+
+    fn depth(navigator: &Navigator) -> usize {
+        navigator.stack.len()
+    }
+
+    #[test]
+    fn enters_the_top_screen() {
+        let (mut ctx, log) = (context(), Log::default());
+        let navigator = Navigator::new(&mut ctx, vec![Stub::new("a", &log), Stub::new("b", &log)]);
+        assert_eq!(depth(&navigator.unwrap()), 2);
+        assert_eq!(events(&log), ["enter b"]);
+        assert!(Navigator::new(&mut ctx, Vec::new()).is_err());
+    }
+
+    #[test]
+    fn pushes_and_pops() {
+        let (mut ctx, log) = (context(), Log::default());
+        let b = Stub::new("b", &log).then(Transition::Pop);
+        let a = Stub::new("a", &log).then(Transition::Push(b));
+        let mut navigator = Navigator::new(&mut ctx, vec![a]).unwrap();
+        events(&log);
+
+        assert!(navigator
+            .handle_input(&mut ctx, Input::Action(Action::None))
+            .unwrap());
+        assert_eq!(depth(&navigator), 2);
+        assert_eq!(events(&log), ["enter b"]);
+
+        assert!(navigator
+            .handle_input(&mut ctx, Input::Action(Action::None))
+            .unwrap());
+        assert_eq!(depth(&navigator), 1);
+        assert_eq!(events(&log), ["exit b", "enter a"]);
+    }
+
+    #[test]
+    fn stays() {
+        let (mut ctx, log) = (context(), Log::default());
+        let mut navigator = Navigator::new(&mut ctx, vec![Stub::new("a", &log)]).unwrap();
+        events(&log);
+        assert!(navigator
+            .handle_input(&mut ctx, Input::Action(Action::None))
+            .unwrap());
+        assert_eq!(depth(&navigator), 1);
+        assert!(events(&log).is_empty());
+    }
+
+    #[test]
+    fn replaces_the_top_screen() {
+        let (mut ctx, log) = (context(), Log::default());
+        let c = Stub::new("c", &log);
+        let b = Stub::new("b", &log).then(Transition::Replace(c));
+        let mut navigator = Navigator::new(&mut ctx, vec![Stub::new("a", &log), b]).unwrap();
+        events(&log);
+
+        assert!(navigator
+            .handle_input(&mut ctx, Input::Action(Action::None))
+            .unwrap());
+        assert_eq!(depth(&navigator), 2);
+        assert_eq!(events(&log), ["exit b", "enter c"]);
+    }
+
+    #[test]
+    fn resets_to_a_single_screen() {
+        let (mut ctx, log) = (context(), Log::default());
+        let c = Stub::new("c", &log);
+        let b = Stub::new("b", &log).then(Transition::Reset(c));
+        let mut navigator = Navigator::new(&mut ctx, vec![Stub::new("a", &log), b]).unwrap();
+        events(&log);
+
+        assert!(navigator
+            .handle_input(&mut ctx, Input::Action(Action::None))
+            .unwrap());
+        assert_eq!(depth(&navigator), 1);
+        assert_eq!(events(&log), ["exit b", "exit a", "enter c"]);
+    }
+
+    #[test]
+    fn quits() {
+        let (mut ctx, log) = (context(), Log::default());
+        let b = Stub::new("b", &log).then(Transition::Quit);
+        let mut navigator = Navigator::new(&mut ctx, vec![Stub::new("a", &log), b]).unwrap();
+        events(&log);
+
+        assert!(!navigator
+            .handle_input(&mut ctx, Input::Action(Action::None))
+            .unwrap());
+        assert_eq!(depth(&navigator), 0);
+        assert_eq!(events(&log), ["exit b", "exit a"]);
+        assert!(!navigator
+            .handle_input(&mut ctx, Input::Action(Action::None))
+            .unwrap());
+    }
+
+    #[test]
+    fn quits_once_the_last_screen_is_popped() {
+        let (mut ctx, log) = (context(), Log::default());
+        let a = Stub::new("a", &log).then(Transition::Pop);
+        let mut navigator = Navigator::new(&mut ctx, vec![a]).unwrap();
+        events(&log);
+
+        assert!(!navigator
+            .handle_input(&mut ctx, Input::Action(Action::None))
+            .unwrap());
+        assert_eq!(events(&log), ["exit a"]);
+    }
+}
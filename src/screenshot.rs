@@ -0,0 +1,27 @@
+use crate::canvas::{mxcfb_rect, Canvas};
+use crate::stats;
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Saves what is shown in `region` as PNG in the home directory.
+/// Returns the path of the new file.
+pub fn save(canvas: &mut Canvas, region: mxcfb_rect) -> Result<PathBuf> {
+    let img = canvas.dump_rgb(region)?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_millis())
+        .unwrap_or(0);
+    // Screenshots taken in quick succession are kept apart
+    let path = (0..)
+        .map(|n| match n {
+            0 => format!("inky-2048-{}.png", timestamp),
+            n => format!("inky-2048-{}-{}.png", timestamp, n),
+        })
+        .map(|name| stats::home_dir().join(name))
+        .find(|path| !path.exists())
+        .expect("Ran out of screenshot names");
+    img.save(&path)
+        .with_context(|| format!("Failed to save screenshot to {:?}", path))?;
+    Ok(path)
+}
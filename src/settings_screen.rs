@@ -0,0 +1,353 @@
+use crate::board_view::RefreshPolicy;
+use crate::canvas::{color, mxcfb_rect, Canvas, Point2, Refresh, Vector2};
+use crate::config::{self, Action, Config};
+use crate::game::{MAX_BOARD_SIZE, MIN_BOARD_SIZE};
+use crate::game_screen;
+use crate::layout::Handedness;
+use crate::screen::{Context, Input, Screen, Transition};
+use crate::swipe::SWIPE_SENSITIVITY_CHUNK_DISTS;
+use crate::tiles::Theme;
+use anyhow::Result;
+use clap::ValueEnum;
+
+const TITLE_FONT_SIZE: f32 = 100.0;
+const ROW_FONT_SIZE: f32 = 50.0;
+const ROW_HEIGHT: u32 = 130;
+/// Top of the first row
+const ROWS_TOP: u32 = 230;
+/// Width of the lines drawn by Canvas::draw_box_button()
+const ROW_LINE_WIDTH: u32 = 5;
+/// Distance of a slider to the left and right edge of the screen
+const SLIDER_MARGIN: u32 = 250;
+/// Center of a slider below the top of its row
+const SLIDER_Y: u32 = 100;
+const SLIDER_KNOB_WIDTH: u32 = 30;
+const SLIDER_KNOB_HEIGHT: u32 = 40;
+const HINT_FONT_SIZE: f32 = 35.0;
+const HINT_MAX_CHARS_PER_LINE: usize = 60;
+const BUTTON_FONT_SIZE: f32 = 60.0;
+const BUTTON_WIDTH: u32 = 350;
+const BUTTON_HEIGHT: u32 = 110;
+/// Space between the bottom of the buttons and the bottom of the screen
+const BUTTONS_BOTTOM_MARGIN: u32 = 50;
+
+/// A row of the settings screen.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Setting {
+    Animations,
+    Refresh,
+    SwipeSensitivity,
+    BoardSize,
+    Theme,
+    Handedness,
+}
+
+const SETTINGS: [Setting; 6] = [
+    Setting::Animations,
+    Setting::Refresh,
+    Setting::SwipeSensitivity,
+    Setting::BoardSize,
+    Setting::Theme,
+    Setting::Handedness,
+];
+
+impl Setting {
+    /// Range of the values shown as a slider
+    fn slider_range(self) -> Option<(usize, usize)> {
+        match self {
+            Setting::SwipeSensitivity => Some((1, SWIPE_SENSITIVITY_CHUNK_DISTS.len())),
+            Setting::BoardSize => Some((MIN_BOARD_SIZE, MAX_BOARD_SIZE)),
+            _ => None,
+        }
+    }
+
+    fn label(self, config: &Config) -> String {
+        match self {
+            Setting::Animations => format!(
+                "Animations: {}",
+                if config.animations { "on" } else { "off" }
+            ),
+            Setting::Refresh => format!("Refresh: {}", name(config.refresh)),
+            Setting::SwipeSensitivity => {
+                format!("Swipe sensitivity: {}", config.swipe.sensitivity())
+            }
+            Setting::BoardSize => format!(
+                "Board size (next game): {}x{}",
+                config.board_size, config.board_size
+            ),
+            Setting::Theme => format!("Theme: {}", name(config.theme)),
+            Setting::Handedness => format!("Handedness: {}", name(config.handedness)),
+        }
+    }
+
+    fn value(self, config: &Config) -> usize {
+        match self {
+            Setting::SwipeSensitivity => config.swipe.sensitivity(),
+            Setting::BoardSize => config.board_size,
+            _ => 0,
+        }
+    }
+
+    /// Toggles and cycles through choices. Sliders are set to `value`.
+    fn change(self, config: &mut Config, value: usize) {
+        match self {
+            Setting::Animations => config.animations = !config.animations,
+            Setting::Refresh => config.refresh = next::<RefreshPolicy>(config.refresh),
+            Setting::SwipeSensitivity => config.swipe.set_sensitivity(value),
+            Setting::BoardSize => config.board_size = value,
+            Setting::Theme => config.theme = next::<Theme>(config.theme),
+            Setting::Handedness => config.handedness = next::<Handedness>(config.handedness),
+        }
+    }
+
+    /// Takes over the value of the setting from `from`.
+    fn copy(self, from: &Config, to: &mut Config) {
+        match self {
+            Setting::Animations => to.animations = from.animations,
+            Setting::Refresh => to.refresh = from.refresh,
+            Setting::SwipeSensitivity => {
+                to.swipe.chunk_distance = from.swipe.chunk_distance;
+                to.swipe.trigger_distance = from.swipe.trigger_distance;
+            }
+            Setting::BoardSize => to.board_size = from.board_size,
+            Setting::Theme => to.theme = from.theme,
+            Setting::Handedness => to.handedness = from.handedness,
+        }
+    }
+}
+
+/// Toggles and sliders for the most important parts of the config.
+/// Done saves the changed ones and applies them to the screen below.
+pub struct SettingsScreen {
+    config: Config,
+    /// Settings tapped so far. Only these are saved and applied,
+    /// the rest may come from the command line.
+    changed: Vec<Setting>,
+    rows: Vec<(mxcfb_rect, Setting)>,
+    /// Why Done didn't apply the settings. Shown instead of the hint.
+    error: Option<String>,
+    done_button: mxcfb_rect,
+    cancel_button: mxcfb_rect,
+}
+
+impl SettingsScreen {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            changed: Vec::new(),
+            rows: Vec::new(),
+            error: None,
+            done_button: mxcfb_rect::invalid(),
+            cancel_button: mxcfb_rect::invalid(),
+        }
+    }
+
+    /// Copies the changed settings into `config`.
+    fn apply(&self, config: &mut Config) {
+        for setting in &self.changed {
+            setting.copy(&self.config, config);
+        }
+    }
+
+    /// Changes the tapped setting.
+    fn handle_tap(&mut self, canvas: &mut Canvas, pos: Point2<u16>) {
+        let (area, setting) = match self
+            .rows
+            .iter()
+            .find(|(area, _)| Canvas::is_hitting(pos, *area))
+        {
+            Some(row) => *row,
+            None => return,
+        };
+        let value = match setting.slider_range() {
+            Some((min, max)) => slider_value(area, min, max, pos.x as u32),
+            None => 0,
+        };
+        setting.change(&mut self.config, value);
+        if !self.changed.contains(&setting) {
+            self.changed.push(setting);
+        }
+        debug!("Changed {:?}: {}", setting, setting.label(&self.config));
+
+        // Only the inside of the row. Its lines are shared with the neighbors.
+        canvas.fill_rect(
+            Point2 {
+                x: Some(area.left as i32),
+                y: Some((area.top + ROW_LINE_WIDTH) as i32),
+            },
+            Vector2 {
+                x: area.width,
+                y: area.height - ROW_LINE_WIDTH,
+            },
+            color::WHITE,
+        );
+        let update_area = canvas.draw_box_button(
+            area.top as i32,
+            area.height,
+            &setting.label(&self.config),
+            ROW_FONT_SIZE,
+        );
+        draw_slider(canvas, area, setting, &self.config);
+        canvas.update_partial(&update_area, Refresh::Normal);
+    }
+}
+
+impl Screen for SettingsScreen {
+    fn draw(&mut self, ctx: &mut Context) -> Result<()> {
+        let canvas = &mut ctx.canvas;
+        canvas.clear();
+        canvas.draw_text(
+            Point2 {
+                x: None,
+                y: Some(175),
+            },
+            "Settings",
+            TITLE_FONT_SIZE,
+        );
+
+        self.rows.clear();
+        for (i, setting) in SETTINGS.iter().enumerate() {
+            let top = ROWS_TOP + i as u32 * ROW_HEIGHT;
+            let area = canvas.draw_box_button(
+                top as i32,
+                ROW_HEIGHT,
+                &setting.label(&self.config),
+                ROW_FONT_SIZE,
+            );
+            draw_slider(canvas, area, *setting, &self.config);
+            self.rows.push((area, *setting));
+        }
+
+        let hint_y = (ROWS_TOP + SETTINGS.len() as u32 * ROW_HEIGHT + 80) as i32;
+        canvas.draw_multi_line_text(
+            None,
+            hint_y,
+            &self.error.clone().unwrap_or_else(|| {
+                format!(
+                    "Changes are saved to {} and apply when you tap Done.",
+                    config::path().display()
+                )
+            }),
+            HINT_MAX_CHARS_PER_LINE,
+            3,
+            HINT_FONT_SIZE,
+            1.0,
+        );
+
+        let buttons_top = canvas.height() - BUTTONS_BOTTOM_MARGIN - BUTTON_HEIGHT;
+        let center_x = canvas.width() / 2;
+        self.cancel_button = canvas.draw_fixed_button(
+            mxcfb_rect {
+                left: center_x - BUTTON_WIDTH - 50,
+                top: buttons_top,
+                width: BUTTON_WIDTH,
+                height: BUTTON_HEIGHT,
+            },
+            "Cancel",
+            BUTTON_FONT_SIZE,
+        );
+        self.done_button = canvas.draw_fixed_button(
+            mxcfb_rect {
+                left: center_x + 50,
+                top: buttons_top,
+                width: BUTTON_WIDTH,
+                height: BUTTON_HEIGHT,
+            },
+            "Done",
+            BUTTON_FONT_SIZE,
+        );
+        canvas.update_full(Refresh::Quality);
+        Ok(())
+    }
+
+    fn handle_input(&mut self, ctx: &mut Context, input: Input) -> Result<Transition> {
+        Ok(match input {
+            Input::Tap(pos) if Canvas::is_hitting(pos, self.cancel_button) => Transition::Pop,
+            Input::Tap(pos) if Canvas::is_hitting(pos, self.done_button) => {
+                let mut config = ctx.config;
+                self.apply(&mut config);
+                // Invalid settings stay off the game
+                if let Err(e) = config.validate() {
+                    warn!("Not applying the settings: {:?}", e);
+                    self.error = Some(format!("{:#}", e));
+                    self.draw(ctx)?;
+                    return Ok(Transition::Stay);
+                }
+                ctx.config = config;
+                if let Err(e) = Config::edit_file(|config| self.apply(config)) {
+                    error!("Failed to save the settings: {:?}", e);
+                    self.error = Some(format!("Applied but not saved: {:#}", e));
+                    self.draw(ctx)?;
+                    return Ok(Transition::Stay);
+                }
+                Transition::Pop
+            }
+            Input::Tap(pos) => {
+                self.handle_tap(&mut ctx.canvas, pos);
+                Transition::Stay
+            }
+            // Same as Cancel
+            Input::Action(Action::Menu) => Transition::Pop,
+            Input::Action(action) => game_screen::handle_action(ctx, action),
+            _ => Transition::Stay,
+        })
+    }
+}
+
+/// Horizontal extent of the sliders in the given row.
+fn slider_bounds(row: mxcfb_rect) -> (u32, u32) {
+    (
+        row.left + SLIDER_MARGIN,
+        row.left + row.width - SLIDER_MARGIN,
+    )
+}
+
+/// Value of the slider position closest to `x`.
+fn slider_value(row: mxcfb_rect, min: usize, max: usize, x: u32) -> usize {
+    let (left, right) = slider_bounds(row);
+    let x = x.clamp(left, right);
+    let steps = (max - min) as f32;
+    min + ((x - left) as f32 / (right - left) as f32 * steps).round() as usize
+}
+
+/// Track with a knob below the label of slider rows.
+fn draw_slider(canvas: &mut Canvas, row: mxcfb_rect, setting: Setting, config: &Config) {
+    let (min, max) = match setting.slider_range() {
+        Some(range) => range,
+        None => return,
+    };
+    let (left, right) = slider_bounds(row);
+    let y = (row.top + SLIDER_Y) as i32;
+    canvas.draw_line(
+        Point2 { x: left as i32, y },
+        Point2 { x: right as i32, y },
+        3,
+    );
+    let knob_x = left + (right - left) * (setting.value(config) - min) as u32 / (max - min) as u32;
+    canvas.fill_rect(
+        Point2 {
+            x: Some((knob_x - SLIDER_KNOB_WIDTH / 2) as i32),
+            y: Some(y - SLIDER_KNOB_HEIGHT as i32 / 2),
+        },
+        Vector2 {
+            x: SLIDER_KNOB_WIDTH,
+            y: SLIDER_KNOB_HEIGHT,
+        },
+        color::BLACK,
+    );
+}
+
+/// Name as used in the config file and on the command line.
+fn name<T: ValueEnum>(value: T) -> String {
+    value
+        .to_possible_value()
+        .map(|possible_value| possible_value.get_name().to_owned())
+        .unwrap_or_default()
+}
+
+/// The choice after `value`, starting over after the last one.
+fn next<T: ValueEnum + PartialEq + Copy>(value: T) -> T {
+    let variants = T::value_variants();
+    let index = variants.iter().position(|v| *v == value).unwrap_or(0);
+    variants[(index + 1) % variants.len()]
+}
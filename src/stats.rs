@@ -0,0 +1,269 @@
+use crate::game::{Game, Mode, MoveRecord, SpawnedTile};
+use crate::rules::{Rules, DEFAULT_BASE};
+use crate::timer::DEFAULT_TIME_LIMIT_MINUTES;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const GAMES_FILE: &str = "games.jsonl";
+
+/// A finished game as stored in GAMES_FILE (one JSON object per line).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameRecord {
+    pub score: u32,
+    pub max_tile: u32,
+    pub moves: u32,
+    pub duration_secs: u64,
+    pub seed: u64,
+    /// Unix timestamp
+    pub finished_at: u64,
+    pub board_size: usize,
+    pub mode: Mode,
+    /// Equal tiles it took to merge. Only needed for custom-base games.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base: Option<u32>,
+    /// Positions (x, y) of the walls
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub walls: Vec<(usize, usize)>,
+    /// Only time attack games have one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_limit_minutes: Option<u64>,
+    /// Empty for games recorded before moves were stored
+    #[serde(default)]
+    pub start_tiles: Vec<SpawnedTile>,
+    #[serde(default)]
+    pub move_list: Vec<MoveRecord>,
+}
+
+impl GameRecord {
+    pub fn from_game(game: &Game) -> Self {
+        Self {
+            score: game.score,
+            max_tile: game.max_tile(),
+            moves: game.moves,
+            duration_secs: game.duration().as_secs(),
+            seed: game.seed,
+            finished_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|since_epoch| since_epoch.as_secs())
+                .unwrap_or(0),
+            board_size: game.size,
+            mode: game.mode,
+            base: match game.rules {
+                Rules::Powers { base } => Some(base),
+                Rules::Fibonacci => None,
+            },
+            walls: game.walls(),
+            time_limit_minutes: game.time_limit.map(|limit| limit.as_secs() / 60),
+            start_tiles: game.start_tiles.clone(),
+            move_list: game.history.clone(),
+        }
+    }
+
+    pub fn rules(&self) -> Rules {
+        self.mode.rules(self.base.unwrap_or(DEFAULT_BASE))
+    }
+
+    /// The mode as shown to the player. Custom-base games
+    /// include their base and time attack games their limit.
+    pub fn mode_name(&self) -> String {
+        match self.mode {
+            Mode::CustomBase => format!("Base{}", self.base.unwrap_or(DEFAULT_BASE)),
+            Mode::TimeAttack => format!(
+                "TimeAttack{}min",
+                self.time_limit_minutes
+                    .unwrap_or(DEFAULT_TIME_LIMIT_MINUTES)
+            ),
+            mode => format!("{:?}", mode),
+        }
+    }
+
+    pub fn won(&self) -> bool {
+        self.max_tile >= self.rules().winning_tile()
+    }
+}
+
+pub fn home_dir() -> PathBuf {
+    PathBuf::from(std::env::var("HOME").unwrap_or_else(|_| "/home/root".to_owned()))
+}
+
+/// Directory for everything that gets saved.
+pub fn data_dir() -> PathBuf {
+    home_dir().join(".local/share/inky-2048")
+}
+
+pub fn record_game(record: &GameRecord) -> Result<()> {
+    let dir = data_dir();
+    fs::create_dir_all(&dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    let path = dir.join(GAMES_FILE);
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {:?}", path))?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+    Ok(())
+}
+
+/// All recorded games, oldest first. Lines that can't be
+/// parsed are skipped.
+pub fn load_games() -> Result<Vec<GameRecord>> {
+    let path = data_dir().join(GAMES_FILE);
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e).with_context(|| format!("Failed to open {:?}", path)),
+    };
+
+    let mut records = vec![];
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        match serde_json::from_str(&line?) {
+            Ok(record) => records.push(record),
+            Err(e) => warn!("Skipping line {} of {:?}: {}", i + 1, path, e),
+        }
+    }
+    Ok(records)
+}
+
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub games_played: usize,
+    pub best_score: u32,
+    pub average_score: u32,
+    pub win_rate: f32,
+    pub current_streak: usize,
+    pub longest_streak: usize,
+    /// Max tile => Amount of games that ended with it
+    pub max_tiles: BTreeMap<u32, usize>,
+}
+
+impl Stats {
+    pub fn from_records(records: &[GameRecord]) -> Self {
+        let mut stats = Stats {
+            games_played: records.len(),
+            ..Default::default()
+        };
+        if records.is_empty() {
+            return stats;
+        }
+
+        let mut total_score = 0u64;
+        let mut wins = 0;
+        for record in records {
+            stats.best_score = stats.best_score.max(record.score);
+            total_score += record.score as u64;
+            *stats.max_tiles.entry(record.max_tile).or_default() += 1;
+
+            if record.won() {
+                wins += 1;
+                stats.current_streak += 1;
+                stats.longest_streak = stats.longest_streak.max(stats.current_streak);
+            } else {
+                stats.current_streak = 0;
+            }
+        }
+        stats.average_score = (total_score / records.len() as u64) as u32;
+        stats.win_rate = wins as f32 / records.len() as f32;
+        stats
+    }
+
+    /// The numbers as text, one per line.
+    pub fn summary(&self) -> Vec<String> {
+        vec![
+            format!("Games played: {}", self.games_played),
+            format!("Best score: {}", self.best_score),
+            format!("Average score: {}", self.average_score),
+            format!("Win rate: {:.0}%", self.win_rate * 100.0),
+            format!(
+                "Win streak: {} (longest {})",
+                self.current_streak, self.longest_streak
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(score: u32, max_tile: u32) -> GameRecord {
+        GameRecord {
+            score,
+            max_tile,
+            moves: 100,
+            duration_secs: 60,
+            seed: 0,
+            finished_at: 0,
+            board_size: 4,
+            mode: Mode::Classic,
+            base: Some(2),
+            walls: Vec::new(),
+            time_limit_minutes: None,
+            start_tiles: Vec::new(),
+            move_list: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn aggregates_the_records() {
+        let stats = Stats::from_records(&[
+            record(1000, 128),
+            record(3000, 256),
+            record(2000, 128),
+            record(30000, 2048),
+        ]);
+        assert_eq!(stats.games_played, 4);
+        assert_eq!(stats.best_score, 30000);
+        assert_eq!(stats.average_score, 9000);
+        assert_eq!(stats.win_rate, 0.25);
+        assert_eq!(
+            stats.max_tiles.into_iter().collect::<Vec<_>>(),
+            vec![(128, 2), (256, 1), (2048, 1)]
+        );
+    }
+
+    #[test]
+    fn counts_streaks_of_won_games() {
+        let won = || record(20000, 2048);
+        let lost = || record(1000, 128);
+        let stats = Stats::from_records(&[won(), won(), won(), lost(), won(), lost(), won()]);
+        assert_eq!(stats.longest_streak, 3);
+        assert_eq!(stats.current_streak, 1);
+
+        let stats = Stats::from_records(&[lost(), record(40000, 4096), won()]);
+        assert_eq!(stats.longest_streak, 2);
+        assert_eq!(stats.current_streak, 2);
+        assert_eq!(Stats::from_records(&[won(), lost()]).current_streak, 0);
+    }
+
+    #[test]
+    fn names_modes_with_their_settings() {
+        let classic = record(1000, 128);
+        assert_eq!(classic.mode_name(), "Classic");
+        let base = GameRecord {
+            mode: Mode::CustomBase,
+            base: Some(5),
+            ..classic.clone()
+        };
+        assert_eq!(base.mode_name(), "Base5");
+        let time_attack = GameRecord {
+            mode: Mode::TimeAttack,
+            time_limit_minutes: Some(5),
+            ..classic
+        };
+        assert_eq!(time_attack.mode_name(), "TimeAttack5min");
+    }
+
+    #[test]
+    fn has_nothing_to_show_without_records() {
+        let stats = Stats::from_records(&[]);
+        assert_eq!(stats.games_played, 0);
+        assert_eq!(stats.average_score, 0);
+        assert_eq!(stats.win_rate, 0.0);
+        assert!(stats.max_tiles.is_empty());
+    }
+}
@@ -0,0 +1,162 @@
+use crate::canvas::{color, mxcfb_rect, Canvas, Point2, Refresh, Vector2};
+use crate::config::Action;
+use crate::game_screen;
+use crate::screen::{Context, Input, Screen, Transition};
+use crate::stats::{self, Stats};
+use anyhow::Result;
+
+const TITLE_FONT_SIZE: f32 = 100.0;
+const HEADLINE_FONT_SIZE: f32 = 60.0;
+const LINE_FONT_SIZE: f32 = 50.0;
+const LINE_HEIGHT: i32 = 75;
+const HISTOGRAM_LABEL_X: i32 = 150;
+const HISTOGRAM_BAR_X: i32 = 400;
+const HISTOGRAM_BAR_MAX_WIDTH: u32 = 750;
+const HISTOGRAM_BAR_HEIGHT: u32 = 40;
+/// Histogram rows below this won't be drawn anymore since
+/// they would collide with the footer.
+const HISTOGRAM_MAX_Y: i32 = 1680;
+const BUTTON_GAP: u32 = 20;
+
+/// Stats of all recorded games with a "Back" button.
+pub struct StatsScreen {
+    back_button: mxcfb_rect,
+}
+
+impl StatsScreen {
+    pub fn new() -> Self {
+        Self {
+            back_button: mxcfb_rect::invalid(),
+        }
+    }
+}
+
+impl Screen for StatsScreen {
+    fn draw(&mut self, ctx: &mut Context) -> Result<()> {
+        let records = stats::load_games().unwrap_or_else(|e| {
+            error!("Failed to load recorded games: {:?}", e);
+            Vec::new()
+        });
+        self.back_button = draw(
+            &mut ctx.canvas,
+            &Stats::from_records(&records),
+            None,
+            "Back",
+        );
+        Ok(())
+    }
+
+    fn handle_input(&mut self, ctx: &mut Context, input: Input) -> Result<Transition> {
+        Ok(match input {
+            Input::Tap(pos) if Canvas::is_hitting(pos, self.back_button) => Transition::Pop,
+            Input::Action(Action::Menu) => Transition::Pop,
+            Input::Action(action) => game_screen::handle_action(ctx, action),
+            _ => Transition::Stay,
+        })
+    }
+}
+
+/// Draws the stats onto a cleared screen. The `headline` is shown
+/// below the title (e.g. the score of the game that just ended).
+/// Returns the hitbox of the button labeled `button`.
+pub fn draw(
+    canvas: &mut Canvas,
+    stats: &Stats,
+    headline: Option<&str>,
+    button: &str,
+) -> mxcfb_rect {
+    canvas.clear();
+    canvas.draw_text(
+        Point2 {
+            x: None,
+            y: Some(175),
+        },
+        "Statistics",
+        TITLE_FONT_SIZE,
+    );
+    if let Some(headline) = headline {
+        canvas.draw_text(
+            Point2 {
+                x: None,
+                y: Some(300),
+            },
+            headline,
+            HEADLINE_FONT_SIZE,
+        );
+    }
+
+    let lines = stats.summary();
+    let mut y = 450;
+    for line in lines.iter() {
+        canvas.draw_text(
+            Point2 {
+                x: Some(HISTOGRAM_LABEL_X),
+                y: Some(y),
+            },
+            line,
+            LINE_FONT_SIZE,
+        );
+        y += LINE_HEIGHT;
+    }
+
+    y += LINE_HEIGHT;
+    canvas.draw_text(
+        Point2 {
+            x: Some(HISTOGRAM_LABEL_X),
+            y: Some(y),
+        },
+        "Highest tile per game",
+        HEADLINE_FONT_SIZE,
+    );
+    y += LINE_HEIGHT;
+
+    let most_games = stats.max_tiles.values().copied().max().unwrap_or(0).max(1);
+    // Highest tiles first
+    for (tile, games) in stats.max_tiles.iter().rev() {
+        if y > HISTOGRAM_MAX_Y {
+            break;
+        }
+        canvas.draw_text(
+            Point2 {
+                x: Some(HISTOGRAM_LABEL_X),
+                y: Some(y),
+            },
+            &tile.to_string(),
+            LINE_FONT_SIZE,
+        );
+        let bar_width = (HISTOGRAM_BAR_MAX_WIDTH * *games as u32 / most_games as u32).max(1);
+        canvas.fill_rect(
+            Point2 {
+                x: Some(HISTOGRAM_BAR_X),
+                y: Some(y - HISTOGRAM_BAR_HEIGHT as i32),
+            },
+            Vector2 {
+                x: bar_width,
+                y: HISTOGRAM_BAR_HEIGHT,
+            },
+            color::BLACK,
+        );
+        canvas.draw_text(
+            Point2 {
+                x: Some(HISTOGRAM_BAR_X + bar_width as i32 + 20),
+                y: Some(y),
+            },
+            &games.to_string(),
+            LINE_FONT_SIZE,
+        );
+        y += LINE_HEIGHT;
+    }
+
+    let button = canvas.draw_button(
+        Point2 {
+            x: None,
+            y: Some(canvas.height() as i32 - 80),
+        },
+        button,
+        LINE_FONT_SIZE,
+        BUTTON_GAP,
+        BUTTON_GAP,
+    );
+    canvas.update_full(Refresh::Quality);
+    button
+}
@@ -1,6 +1,9 @@
+use crate::canvas::{mxcfb_rect, Canvas, Orientation};
+use anyhow::Result;
 use fxhash::FxHashMap;
 use libremarkable::cgmath::Point2;
 use libremarkable::input::multitouch::MultitouchEvent;
+use serde::{Deserialize, Serialize};
 
 pub const SWIPE_DIRECTION_CHUNK_DIST: u16 = 25; // px
 /// Min TrackedFinger.swipe_chunk_count to cound as completed swipe
@@ -19,6 +22,83 @@ pub const SWIPE_DIRECTION_MIN_RATIO: f32 = 1.5;
 /// swipe was messed up.
 pub const SWIPE_ALLOW_MULTIPLE_SWIPES_AT_ONCE: bool = false;
 
+/// SwipeSettings::chunk_distance per sensitivity, from needing
+/// long swipes (1) to very short ones (5). 3 is the default.
+pub const SWIPE_SENSITIVITY_CHUNK_DISTS: [u16; 5] = [45, 35, 25, 18, 12];
+
+/// Thresholds of the swipe detection. Default to the constants above.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct SwipeSettings {
+    /// See SWIPE_DIRECTION_CHUNK_DIST
+    pub chunk_distance: u16,
+    /// See SWIPE_DIRECTION_COMPLETED_MIN_CHUNK_COUNT
+    pub min_chunks: u16,
+    /// See SWIPE_DIRECTION_MIN_RATIO
+    pub min_ratio: f32,
+    /// Swipes fire once the finger moved this far (see
+    /// Trigger::MinDistance) instead of when it's released.
+    pub trigger_distance: Option<u16>,
+}
+
+impl Default for SwipeSettings {
+    fn default() -> Self {
+        Self {
+            chunk_distance: SWIPE_DIRECTION_CHUNK_DIST,
+            min_chunks: SWIPE_DIRECTION_COMPLETED_MIN_CHUNK_COUNT,
+            min_ratio: SWIPE_DIRECTION_MIN_RATIO,
+            trigger_distance: None,
+        }
+    }
+}
+
+impl SwipeSettings {
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            self.chunk_distance > 0,
+            "chunk-distance needs to be positive"
+        );
+        ensure!(
+            self.min_ratio >= 1.0,
+            "min-ratio below 1 would allow diagonal swipes"
+        );
+        if let Some(distance) = self.trigger_distance {
+            ensure!(
+                distance >= self.chunk_distance,
+                "trigger-distance needs to be at least chunk-distance"
+            );
+        }
+        Ok(())
+    }
+
+    /// See SWIPE_SENSITIVITY_CHUNK_DISTS. The closest one is
+    /// picked for custom distances.
+    pub fn sensitivity(&self) -> usize {
+        SWIPE_SENSITIVITY_CHUNK_DISTS
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, dist)| (**dist as i32 - self.chunk_distance as i32).abs())
+            .map(|(i, _)| i + 1)
+            .unwrap_or(1)
+    }
+
+    pub fn set_sensitivity(&mut self, sensitivity: usize) {
+        let index = sensitivity.clamp(1, SWIPE_SENSITIVITY_CHUNK_DISTS.len()) - 1;
+        self.chunk_distance = SWIPE_SENSITIVITY_CHUNK_DISTS[index];
+        // Has to stay valid
+        self.trigger_distance = self
+            .trigger_distance
+            .map(|distance| distance.max(self.chunk_distance));
+    }
+
+    pub fn trigger(&self) -> Trigger {
+        match self.trigger_distance {
+            Some(distance) => Trigger::MinDistance(distance),
+            None => Trigger::Completed,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Trigger {
     /// The swipe gesture will fire, when the user
@@ -66,14 +146,14 @@ impl TrackedFinger {
         }
     }
     fn highest_orthogonal_dist(&self) -> u16 {
-        let x_dist = (self.current_pos.x as i16 - self.last_pos.x as i16).abs() as u16;
-        let y_dist = (self.current_pos.y as i16 - self.last_pos.y as i16).abs() as u16;
+        let x_dist = (self.current_pos.x as i16 - self.last_pos.x as i16).unsigned_abs();
+        let y_dist = (self.current_pos.y as i16 - self.last_pos.y as i16).unsigned_abs();
         std::cmp::max(x_dist, y_dist)
     }
 
-    fn update(&mut self, current_pos: Point2<u16>) {
+    fn update(&mut self, current_pos: Point2<u16>, settings: &SwipeSettings) {
         self.current_pos = current_pos;
-        if self.highest_orthogonal_dist() < SWIPE_DIRECTION_CHUNK_DIST {
+        if self.highest_orthogonal_dist() < settings.chunk_distance {
             return;
         }
         if self.invalidated && !SWIPE_ALLOW_MULTIPLE_SWIPES_AT_ONCE {
@@ -87,19 +167,19 @@ impl TrackedFinger {
         let y_dist = self.current_pos.y as i16 - self.last_pos.y as i16;
 
         let current_dir = if x_dist > y_dist.abs()
-            && x_dist.abs() as f32 / y_dist.abs().max(1) as f32 > SWIPE_DIRECTION_MIN_RATIO
+            && x_dist.abs() as f32 / y_dist.abs().max(1) as f32 > settings.min_ratio
         {
             Some(Direction::Right)
         } else if -x_dist > y_dist.abs()
-            && x_dist.abs() as f32 / y_dist.abs().max(1) as f32 > SWIPE_DIRECTION_MIN_RATIO
+            && x_dist.abs() as f32 / y_dist.abs().max(1) as f32 > settings.min_ratio
         {
             Some(Direction::Left)
         } else if y_dist > x_dist.abs()
-            && y_dist.abs() as f32 / x_dist.abs().max(1) as f32 > SWIPE_DIRECTION_MIN_RATIO
+            && y_dist.abs() as f32 / x_dist.abs().max(1) as f32 > settings.min_ratio
         {
             Some(Direction::Down)
         } else if -y_dist > x_dist.abs()
-            && y_dist.abs() as f32 / x_dist.abs().max(1) as f32 > SWIPE_DIRECTION_MIN_RATIO
+            && y_dist.abs() as f32 / x_dist.abs().max(1) as f32 > settings.min_ratio
         {
             Some(Direction::Up)
         } else {
@@ -135,15 +215,22 @@ impl TrackedFinger {
 
 pub struct SwipeTracker {
     trackings: FxHashMap<i32 /* Tracking id */, TrackedFinger>,
+    settings: SwipeSettings,
 }
 
 impl SwipeTracker {
-    pub fn new() -> Self {
+    pub fn new(settings: SwipeSettings) -> Self {
         Self {
             trackings: Default::default(),
+            settings,
         }
     }
 
+    /// Applies to fingers that are already being tracked as well.
+    pub fn set_settings(&mut self, settings: SwipeSettings) {
+        self.settings = settings;
+    }
+
     pub fn detect<'a>(
         &mut self,
         event: MultitouchEvent,
@@ -155,17 +242,18 @@ impl SwipeTracker {
                     .insert(finger.tracking_id, TrackedFinger::new(finger.pos));
             }
             MultitouchEvent::Move { finger } => {
+                let settings = &self.settings;
                 let mut potential_swipe: Option<&'a Swipe> = None;
                 self.trackings
                     .entry(finger.tracking_id)
-                    .and_modify(|tracked_finger| tracked_finger.update(finger.pos))
+                    .and_modify(|tracked_finger| tracked_finger.update(finger.pos, settings))
                     .and_modify(|tracked_finger| {
                         if let Some(intermediate_direction) = tracked_finger.direction {
                             for swipe in conditions.iter() {
                                 if swipe.direction == intermediate_direction {
                                     if let Trigger::MinDistance(min_dist) = swipe.trigger {
                                         if min_dist
-                                            <= SWIPE_DIRECTION_CHUNK_DIST
+                                            <= settings.chunk_distance
                                                 * tracked_finger.swipe_chunk_count
                                         {
                                             // Reset swipe info
@@ -186,8 +274,7 @@ impl SwipeTracker {
             }
             MultitouchEvent::Release { finger } => {
                 if let Some(tracked_finger) = self.trackings.remove(&finger.tracking_id) {
-                    if tracked_finger.swipe_chunk_count >= SWIPE_DIRECTION_COMPLETED_MIN_CHUNK_COUNT
-                    {
+                    if tracked_finger.swipe_chunk_count >= self.settings.min_chunks {
                         if let Some(completed_direction) = tracked_finger.direction {
                             for swipe in conditions.iter() {
                                 if swipe.direction == completed_direction
@@ -208,3 +295,176 @@ impl SwipeTracker {
         None
     }
 }
+
+/// Routes each finger to the SwipeTracker of the region of the
+/// touchscreen it was pressed in, so that swipes in different
+/// regions are told apart.
+pub struct RegionSwipeTracker {
+    /// Areas in touchscreen coordinates with their trackers
+    regions: Vec<(mxcfb_rect, SwipeTracker)>,
+    /// Tracking id => index of the region
+    fingers: FxHashMap<i32, usize>,
+}
+
+impl RegionSwipeTracker {
+    pub fn new(regions: Vec<mxcfb_rect>, settings: SwipeSettings) -> Self {
+        Self {
+            regions: regions
+                .into_iter()
+                .map(|region| (region, SwipeTracker::new(settings)))
+                .collect(),
+            fingers: Default::default(),
+        }
+    }
+
+    /// The top and the bottom half of the touchscreen, which are
+    /// the left and the right half in landscape.
+    pub fn halves(settings: SwipeSettings) -> Self {
+        // The touchscreen doesn't rotate
+        let size = Orientation::Portrait.size();
+        let half = |top| mxcfb_rect {
+            left: 0,
+            top,
+            width: size.x,
+            height: size.y / 2,
+        };
+        Self::new(vec![half(0), half(size.y / 2)], settings)
+    }
+
+    pub fn set_settings(&mut self, settings: SwipeSettings) {
+        for (_, tracker) in &mut self.regions {
+            tracker.set_settings(settings);
+        }
+    }
+
+    /// Like SwipeTracker::detect(). Also returns the index
+    /// of the region the swipe happened in.
+    pub fn detect<'a>(
+        &mut self,
+        event: MultitouchEvent,
+        conditions: &'a [Swipe],
+    ) -> Option<(usize, &'a Swipe)> {
+        let region = match event {
+            MultitouchEvent::Press { finger } => {
+                let region = self
+                    .regions
+                    .iter()
+                    .position(|(area, _)| Canvas::is_hitting(finger.pos, *area))?;
+                self.fingers.insert(finger.tracking_id, region);
+                region
+            }
+            MultitouchEvent::Move { finger } => *self.fingers.get(&finger.tracking_id)?,
+            MultitouchEvent::Release { finger } => self.fingers.remove(&finger.tracking_id)?,
+            _ => return None,
+        };
+        let (_, tracker) = &mut self.regions[region];
+        tracker
+            .detect(event, conditions)
+            .map(|swipe| (region, swipe))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libremarkable::input::multitouch::Finger;
+
+    const SWIPES: [Swipe; 2] = [
+        Swipe {
+            direction: Direction::Left,
+            trigger: Trigger::Completed,
+        },
+        Swipe {
+            direction: Direction::Right,
+            trigger: Trigger::Completed,
+        },
+    ];
+
+    fn finger(tracking_id: i32, x: u16, y: u16) -> Finger {
+        let mut finger = Finger::default();
+        finger.tracking_id = tracking_id;
+        finger.pos = Point2 { x, y };
+        finger
+    }
+
+    /// Moves both fingers sideways at once, in opposite directions.
+    fn swipe_apart(
+        tracker: &mut RegionSwipeTracker,
+        (x, y1): (u16, u16),
+        y2: u16,
+    ) -> Vec<(usize, Direction)> {
+        let mut detect = |event| {
+            tracker
+                .detect(event, &SWIPES)
+                .map(|(region, swipe)| (region, swipe.direction))
+        };
+        let mut detected = Vec::new();
+        detected.push(detect(MultitouchEvent::Press {
+            finger: finger(1, x, y1),
+        }));
+        detected.push(detect(MultitouchEvent::Press {
+            finger: finger(2, x, y2),
+        }));
+        for step in 1..=4 {
+            let dist = step * SWIPE_DIRECTION_CHUNK_DIST;
+            detected.push(detect(MultitouchEvent::Move {
+                finger: finger(1, x - dist, y1),
+            }));
+            detected.push(detect(MultitouchEvent::Move {
+                finger: finger(2, x + dist, y2),
+            }));
+        }
+        detected.push(detect(MultitouchEvent::Release {
+            finger: finger(1, x - 4 * SWIPE_DIRECTION_CHUNK_DIST, y1),
+        }));
+        detected.push(detect(MultitouchEvent::Release {
+            finger: finger(2, x + 4 * SWIPE_DIRECTION_CHUNK_DIST, y2),
+        }));
+        detected.into_iter().flatten().collect()
+    }
+
+    #[test]
+    fn tells_the_halves_apart() {
+        let mut tracker = RegionSwipeTracker::halves(SwipeSettings::default());
+        let height = Orientation::Portrait.size().y as u16;
+        assert_eq!(
+            swipe_apart(&mut tracker, (700, height / 4), height * 3 / 4),
+            vec![(0, Direction::Left), (1, Direction::Right)]
+        );
+    }
+
+    #[test]
+    fn keeps_fingers_in_the_region_they_were_pressed_in() {
+        let mut tracker = RegionSwipeTracker::halves(SwipeSettings::default());
+        let height = Orientation::Portrait.size().y as u16;
+        let mut detected = Vec::new();
+        let mut y = height / 2 - 10;
+        detected.push(tracker.detect(
+            MultitouchEvent::Press {
+                finger: finger(1, 700, y),
+            },
+            &SWIPES,
+        ));
+        for _ in 0..4 {
+            y += SWIPE_DIRECTION_CHUNK_DIST;
+            detected.push(tracker.detect(
+                MultitouchEvent::Move {
+                    finger: finger(1, 700, y),
+                },
+                &SWIPES,
+            ));
+        }
+        assert!(detected.iter().all(Option::is_none));
+        let swipes = [Swipe {
+            direction: Direction::Down,
+            trigger: Trigger::Completed,
+        }];
+        let released = tracker.detect(
+            MultitouchEvent::Release {
+                finger: finger(1, 700, y),
+            },
+            &swipes,
+        );
+        assert_eq!(released.map(|(region, _)| region), Some(0));
+    }
+}
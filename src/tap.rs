@@ -0,0 +1,49 @@
+use fxhash::FxHashMap;
+use libremarkable::cgmath::Point2;
+use libremarkable::input::multitouch::MultitouchEvent;
+
+/// A finger may move at most this far between pressing and
+/// releasing to still count as a tap.
+pub const TAP_MAX_DIST: u16 = 20; // px
+
+pub struct TapTracker {
+    presses: FxHashMap<i32 /* Tracking id */, (Point2<u16>, bool /* Still valid */)>,
+}
+
+impl TapTracker {
+    pub fn new() -> Self {
+        Self {
+            presses: Default::default(),
+        }
+    }
+
+    /// Returns the position of the press once a finger was released
+    /// without moving away too far.
+    pub fn detect(&mut self, event: MultitouchEvent) -> Option<Point2<u16>> {
+        match event {
+            MultitouchEvent::Press { finger } => {
+                self.presses.insert(finger.tracking_id, (finger.pos, true));
+            }
+            MultitouchEvent::Move { finger } => {
+                if let Some((start_pos, valid)) = self.presses.get_mut(&finger.tracking_id) {
+                    if distance(*start_pos, finger.pos) > TAP_MAX_DIST {
+                        *valid = false;
+                    }
+                }
+            }
+            MultitouchEvent::Release { finger } => {
+                if let Some((start_pos, true)) = self.presses.remove(&finger.tracking_id) {
+                    return Some(start_pos);
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+fn distance(a: Point2<u16>, b: Point2<u16>) -> u16 {
+    let x_dist = (a.x as i32 - b.x as i32).unsigned_abs();
+    let y_dist = (a.y as i32 - b.y as i32).unsigned_abs();
+    x_dist.max(y_dist) as u16
+}
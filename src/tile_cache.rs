@@ -0,0 +1,72 @@
+use crate::canvas::{Canvas, NativeImage};
+use crate::dither::Dithering;
+use crate::rules::Rules;
+use crate::tiles::{self, Theme};
+use fxhash::FxHashMap;
+use std::time::Instant;
+
+/// Highest rank that gets prerendered (32768 in classic games).
+const PRERENDER_MAX_RANK: u32 = 15;
+
+/// Completely composed tiles (background + number) in the native
+/// format of the framebuffer. Rendering a tile from scratch involves
+/// scaling, alpha blending and font rasterizing and is too slow
+/// to be done on every move.
+pub struct TileCache {
+    theme: Theme,
+    dithering: Dithering,
+    /// Tiles are drawn based on their rank
+    rules: Rules,
+    tiles: FxHashMap<(u32 /* Value */, u32 /* Cell size */), NativeImage>,
+}
+
+impl TileCache {
+    pub fn new(theme: Theme, dithering: Dithering, rules: Rules) -> Self {
+        Self {
+            theme,
+            dithering,
+            rules,
+            tiles: Default::default(),
+        }
+    }
+
+    pub fn matches(&self, theme: Theme, dithering: Dithering, rules: Rules) -> bool {
+        self.theme == theme && self.dithering == dithering && self.rules == rules
+    }
+
+    /// Renders the empty tile and all tiles up to PRERENDER_MAX_RANK
+    /// for the given cell size.
+    pub fn prerender(&mut self, canvas: &mut Canvas, cell_size: u32) {
+        let start = Instant::now();
+        self.get(canvas, 0, cell_size);
+        for rank in 1..=PRERENDER_MAX_RANK {
+            if let Some(value) = self.rules.value(rank) {
+                self.get(canvas, value, cell_size);
+            }
+        }
+        info!(
+            "Prerendered {} tiles for cell size {} in {:?}",
+            PRERENDER_MAX_RANK + 1,
+            cell_size,
+            start.elapsed()
+        );
+    }
+
+    /// Returns the tile and renders it first, should it not be cached yet.
+    pub fn get(&mut self, canvas: &mut Canvas, value: u32, cell_size: u32) -> &NativeImage {
+        let (theme, dithering, rules) = (self.theme, self.dithering, self.rules);
+        self.tiles.entry((value, cell_size)).or_insert_with(|| {
+            let start = Instant::now();
+            let rank = rules.rank(value);
+            let tile = tiles::render_tile(canvas, value, rank, cell_size, theme, dithering);
+            let tile = canvas.native_image(&tile);
+            debug!(
+                "Rendered tile {} with size {} in {:?}",
+                value,
+                cell_size,
+                start.elapsed()
+            );
+            tile
+        })
+    }
+}
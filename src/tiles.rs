@@ -0,0 +1,352 @@
+use crate::canvas::{Canvas, Point2};
+use crate::dither::Dithering;
+use libremarkable::image::{imageops, DynamicImage, FilterType, Rgb, RgbImage, RgbaImage};
+use once_cell::sync::OnceCell;
+use std::time::Instant;
+use std::{env, thread};
+
+/// An embedded PNG that is decoded the first time it's needed.
+struct Png<T> {
+    bytes: &'static [u8],
+    image: OnceCell<T>,
+}
+
+impl<T> Png<T> {
+    const fn new(bytes: &'static [u8]) -> Self {
+        Self {
+            bytes,
+            image: OnceCell::new(),
+        }
+    }
+
+    /// Blocks while another thread decodes the image.
+    fn get(&self, convert: fn(DynamicImage) -> T) -> &T {
+        self.image.get_or_init(|| {
+            let start = Instant::now();
+            let image = convert(libremarkable::image::load_from_memory(self.bytes).unwrap());
+            debug!("Decoded an image in {:?}", start.elapsed());
+            image
+        })
+    }
+}
+
+/// Number images by value.
+#[rustfmt::skip]
+static NUMBER_IMAGES: [(u32, Png<RgbaImage>); 15] = [
+    (2, Png::new(include_bytes!("../res/2.png"))),
+    (4, Png::new(include_bytes!("../res/4.png"))),
+    (8, Png::new(include_bytes!("../res/8.png"))),
+    (16, Png::new(include_bytes!("../res/16.png"))),
+    (32, Png::new(include_bytes!("../res/32.png"))),
+    (64, Png::new(include_bytes!("../res/64.png"))),
+    (128, Png::new(include_bytes!("../res/128.png"))),
+    (256, Png::new(include_bytes!("../res/256.png"))),
+    (512, Png::new(include_bytes!("../res/512.png"))),
+    (1024, Png::new(include_bytes!("../res/1024.png"))),
+    (2048, Png::new(include_bytes!("../res/2048.png"))),
+    (4096, Png::new(include_bytes!("../res/4096.png"))),
+    (8192, Png::new(include_bytes!("../res/8192.png"))),
+    (16384, Png::new(include_bytes!("../res/16384.png"))),
+    (32768, Png::new(include_bytes!("../res/32768.png"))),
+];
+
+/// Backgrounds by rank (see Rules::rank()), starting with rank 1.
+/// They get darker the higher the tile.
+#[rustfmt::skip]
+static BG_IMAGES: [Png<RgbImage>; 15] = [
+    Png::new(include_bytes!("../res/bg_2.png")),
+    Png::new(include_bytes!("../res/bg_4.png")),
+    Png::new(include_bytes!("../res/bg_8.png")),
+    Png::new(include_bytes!("../res/bg_16.png")),
+    Png::new(include_bytes!("../res/bg_32.png")),
+    Png::new(include_bytes!("../res/bg_64.png")),
+    Png::new(include_bytes!("../res/bg_128.png")),
+    Png::new(include_bytes!("../res/bg_256.png")),
+    Png::new(include_bytes!("../res/bg_512.png")),
+    Png::new(include_bytes!("../res/bg_1024.png")),
+    Png::new(include_bytes!("../res/bg_2048.png")),
+    Png::new(include_bytes!("../res/bg_4096.png")),
+    Png::new(include_bytes!("../res/bg_8192.png")),
+    Png::new(include_bytes!("../res/bg_16384.png")),
+    Png::new(include_bytes!("../res/bg_32768.png")),
+];
+
+/// Backgrounds before they got dithered. Used when dithering
+/// is done by ourselves.
+#[rustfmt::skip]
+static ORIGINAL_BG_IMAGES: [Png<RgbImage>; 15] = [
+    Png::new(include_bytes!("../res/.pre_bn_dither/bg_2.png")),
+    Png::new(include_bytes!("../res/.pre_bn_dither/bg_4.png")),
+    Png::new(include_bytes!("../res/.pre_bn_dither/bg_8.png")),
+    Png::new(include_bytes!("../res/.pre_bn_dither/bg_16.png")),
+    Png::new(include_bytes!("../res/.pre_bn_dither/bg_32.png")),
+    Png::new(include_bytes!("../res/.pre_bn_dither/bg_64.png")),
+    Png::new(include_bytes!("../res/.pre_bn_dither/bg_128.png")),
+    Png::new(include_bytes!("../res/.pre_bn_dither/bg_256.png")),
+    Png::new(include_bytes!("../res/.pre_bn_dither/bg_512.png")),
+    Png::new(include_bytes!("../res/.pre_bn_dither/bg_1024.png")),
+    Png::new(include_bytes!("../res/.pre_bn_dither/bg_2048.png")),
+    Png::new(include_bytes!("../res/.pre_bn_dither/bg_4096.png")),
+    Png::new(include_bytes!("../res/.pre_bn_dither/bg_8192.png")),
+    Png::new(include_bytes!("../res/.pre_bn_dither/bg_16384.png")),
+    Png::new(include_bytes!("../res/.pre_bn_dither/bg_32768.png")),
+];
+
+fn number_image(number: u32) -> Option<&'static RgbaImage> {
+    let (_, png) = NUMBER_IMAGES.iter().find(|(value, _)| *value == number)?;
+    Some(png.get(|image| image.to_rgba()))
+}
+
+fn background(rank: u32, dithering: Dithering) -> Option<&'static RgbImage> {
+    let pngs = if dithering.wants_original_art() {
+        &ORIGINAL_BG_IMAGES
+    } else {
+        &BG_IMAGES
+    };
+    let png = pngs.get(rank.checked_sub(1)? as usize)?;
+    Some(png.get(|image| image.to_rgb()))
+}
+
+/// Cell size the images in res/ were made for. Other
+/// cell sizes will get scaled images.
+const ART_CELL_SIZE: u32 = 320;
+
+/// Padding between the cell border and the text of a text tile.
+/// Like the border, this is scaled for other cell sizes.
+const TEXT_TILE_PADDING: u32 = 24;
+/// Text should not get higher than this fraction of the cell.
+/// Otherwise single digits would fill up the entire cell while
+/// longer numbers are much smaller.
+const TEXT_TILE_MAX_HEIGHT_RATIO: f32 = 0.45;
+/// Smallest font size that will be considered. Anything below that
+/// is not really readable anymore.
+const TEXT_TILE_MIN_FONT_SIZE: u32 = 12;
+const TEXT_TILE_BORDER: u32 = 3;
+/// Like TEXT_TILE_MAX_HEIGHT_RATIO for the labels on backgrounds,
+/// which need to leave some of the background visible.
+const LABEL_MAX_HEIGHT_RATIO: f32 = 0.3;
+const LABEL_MAX_WIDTH_RATIO: f32 = 0.6;
+
+#[derive(Debug, Copy, Clone, PartialEq, clap::ValueEnum)]
+pub enum Theme {
+    /// The prerendered images in res/. Values without a number
+    /// image are written onto the background of their rank and
+    /// ranks without a background are rendered as text.
+    Images,
+    /// Only plain text.
+    Text,
+}
+
+impl Theme {
+    /// Reads the theme from INKY_2048_THEME (images or text).
+    pub fn from_env() -> Self {
+        match env::var("INKY_2048_THEME").as_deref() {
+            Ok("text") => Theme::Text,
+            Ok("images") | Err(_) => Theme::Images,
+            Ok(other) => {
+                warn!("Unknown theme {:?}. Using images.", other);
+                Theme::Images
+            }
+        }
+    }
+}
+
+/// Decodes the images in the background, smallest tiles first.
+/// Images are decoded on first use anyway, so drawing only has
+/// to wait for the tiles it needs, and not at all once this is done.
+pub fn decode_images_in_background(dithering: Dithering) {
+    thread::spawn(move || {
+        let start = Instant::now();
+        for rank in 1..=NUMBER_IMAGES.len() as u32 {
+            background(rank, dithering);
+            number_image(NUMBER_IMAGES[rank as usize - 1].0);
+        }
+        info!("Decoded the tile images in {:?}", start.elapsed());
+    });
+}
+
+/// Renders the complete tile for `number` with the given cell size.
+/// The rank picks the background (see Rules::rank()). A tile for 0
+/// is just white.
+pub fn render_tile(
+    canvas: &mut Canvas,
+    number: u32,
+    rank: Option<u32>,
+    size: u32,
+    theme: Theme,
+    dithering: Dithering,
+) -> RgbImage {
+    let mut tile = RgbImage::from_pixel(size, size, Rgb([255, 255, 255]));
+    if number == 0 {
+        return tile;
+    }
+
+    match theme {
+        Theme::Images => match rank.and_then(|rank| background(rank, dithering)) {
+            Some(bg) => render_image_tile(canvas, &mut tile, number, bg),
+            None => {
+                debug!("No background for {}. Falling back to text.", number);
+                render_text_tile(canvas, &mut tile, number);
+            }
+        },
+        Theme::Text => render_text_tile(canvas, &mut tile, number),
+    }
+    dithering.apply(&mut tile);
+    tile
+}
+
+/// Scales `value` which is meant for a cell of ART_CELL_SIZE
+/// to a cell of `size`.
+fn scaled(value: u32, size: u32) -> u32 {
+    value * size / ART_CELL_SIZE
+}
+
+/// The background with the number image on top. Numbers without
+/// an image are written onto a white label instead.
+fn render_image_tile(canvas: &mut Canvas, tile: &mut RgbImage, number: u32, bg: &RgbImage) {
+    let size = tile.width();
+    let bg = if size == ART_CELL_SIZE {
+        bg.clone()
+    } else {
+        imageops::resize(
+            bg,
+            scaled(bg.width(), size),
+            scaled(bg.height(), size),
+            FilterType::Triangle,
+        )
+    };
+    let bg_x = (size - bg.width()) / 2;
+    let bg_y = (size - bg.height()) / 2;
+    for (x, y, pixel) in bg.enumerate_pixels() {
+        tile.put_pixel(bg_x + x, bg_y + y, *pixel);
+    }
+
+    let img = match number_image(number) {
+        Some(img) if size == ART_CELL_SIZE => img.clone(),
+        Some(img) => imageops::resize(
+            img,
+            scaled(img.width(), size),
+            scaled(img.height(), size),
+            FilterType::Triangle,
+        ),
+        None => {
+            render_label(canvas, tile, number);
+            return;
+        }
+    };
+
+    // Alpha blend the number on top of the background
+    let img_x = (size - img.width()) / 2;
+    let img_y = (size - img.height()) / 2;
+    for (x, y, pixel) in img.enumerate_pixels() {
+        let alpha = pixel[3] as u16;
+        let under = tile.get_pixel_mut(img_x + x, img_y + y);
+        for channel in 0..3 {
+            under[channel] = ((pixel[channel] as u16 * alpha
+                + under[channel] as u16 * (255 - alpha))
+                / 255) as u8;
+        }
+    }
+}
+
+fn render_text_tile(canvas: &mut Canvas, tile: &mut RgbImage, number: u32) {
+    let size = tile.width();
+
+    // Border
+    let inset = scaled(TEXT_TILE_PADDING, size) / 2;
+    let border = scaled(TEXT_TILE_BORDER, size).max(1);
+    for (x, y, pixel) in tile.enumerate_pixels_mut() {
+        let outer = x >= inset && y >= inset && x < size - inset && y < size - inset;
+        let inner = x >= inset + border
+            && y >= inset + border
+            && x < size - inset - border
+            && y < size - inset - border;
+        if outer && !inner {
+            *pixel = Rgb([0, 0, 0]);
+        }
+    }
+
+    let max_width = size.saturating_sub(scaled(TEXT_TILE_PADDING, size) * 3);
+    let max_height = (size as f32 * TEXT_TILE_MAX_HEIGHT_RATIO) as u32;
+    let font_size = fit_font_size(canvas, &tile_text(number), max_width, max_height);
+    render_number(canvas, tile, number, font_size);
+}
+
+/// The number on a white rectangle in the middle of the tile, so it
+/// stays readable on dark backgrounds.
+fn render_label(canvas: &mut Canvas, tile: &mut RgbImage, number: u32) {
+    let size = tile.width();
+    let text = tile_text(number);
+    let max_width = (size as f32 * LABEL_MAX_WIDTH_RATIO) as u32;
+    let max_height = (size as f32 * LABEL_MAX_HEIGHT_RATIO) as u32;
+    let font_size = fit_font_size(canvas, &text, max_width, max_height);
+    let (text_rect, _) = canvas.measure_text(&text, font_size);
+    let padding = scaled(TEXT_TILE_PADDING, size) / 2;
+    let width = (text_rect.width + 2 * padding).min(size);
+    let height = (text_rect.height + 2 * padding).min(size);
+    let (left, top) = ((size - width) / 2, (size - height) / 2);
+    for y in top..top + height {
+        for x in left..left + width {
+            tile.put_pixel(x, y, Rgb([255, 255, 255]));
+        }
+    }
+    render_number(canvas, tile, number, font_size);
+}
+
+/// Draws the number centered on the tile.
+fn render_number(canvas: &mut Canvas, tile: &mut RgbImage, number: u32, font_size: f32) {
+    let text = tile_text(number);
+    let size = tile.width();
+    let (text_rect, baseline_offset) = canvas.measure_text(&text, font_size);
+
+    // The rect of the dryrun always contains the origin which is
+    // at x = 0. So only the baseline offset needs to be accounted for.
+    let x = (size as i32 - text_rect.width as i32) / 2;
+    let y = (size as i32 - text_rect.height as i32) / 2 - baseline_offset;
+    canvas.render_text(tile, Point2 { x, y }, &text, font_size);
+}
+
+/// The number as written on its tile. Up to six digits fit in full,
+/// longer numbers get shortened (1594323 => 1.6M, 14348907 => 14M)
+/// to stay readable.
+fn tile_text(number: u32) -> String {
+    if number < 1_000_000 {
+        return number.to_string();
+    }
+    let (unit, suffix) = [(1_000_000_000, "G"), (1_000_000, "M")]
+        .into_iter()
+        .find(|(unit, _)| number >= *unit)
+        .unwrap_or((1, ""));
+    let short = number as f32 / unit as f32;
+    if short < 10.0 {
+        format!("{:.1}{}", short, suffix)
+    } else {
+        format!("{}{}", number / unit, suffix)
+    }
+}
+
+/// Finds the largest font size (in whole pixels) at which `text`
+/// still fits into the given dimensions.
+fn fit_font_size(canvas: &mut Canvas, text: &str, max_width: u32, max_height: u32) -> f32 {
+    let fits = |canvas: &mut Canvas, size: u32| {
+        let (rect, _) = canvas.measure_text(text, size as f32);
+        rect.width <= max_width && rect.height <= max_height
+    };
+
+    // Font size roughly corresponds to the line height in pixels
+    // and the text will never be higher than that.
+    let mut low = TEXT_TILE_MIN_FONT_SIZE;
+    let mut high = max_height * 2;
+    if !fits(canvas, low) {
+        return low as f32;
+    }
+    while low < high {
+        let mid = (low + high).div_ceil(2);
+        if fits(canvas, mid) {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    low as f32
+}
@@ -0,0 +1,116 @@
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_TIME_LIMIT_MINUTES: u64 = 3;
+pub const MAX_TIME_LIMIT_MINUTES: u64 = 30;
+/// How often the render thread gets an Input::Tick.
+pub const TICK_INTERVAL: Duration = Duration::from_secs(1);
+/// Longest gap between two updates that counts in full. Ticks update
+/// the clock every second and moves with the longest spawn delay stay
+/// below. Longer gaps mean the app was suspended (for example stopped
+/// by a launcher) or blocked, and only count this much.
+const MAX_UPDATE_GAP: Duration = Duration::from_secs(5);
+
+/// Counts the time in which a game could be played: only while
+/// its board is shown. Time the app was suspended counts only a bit.
+#[derive(Debug, Default)]
+pub struct Clock {
+    elapsed: Duration,
+    /// Last time the elapsed time was updated. None while paused.
+    running_since: Option<Instant>,
+}
+
+impl Clock {
+    /// A paused clock. See resume().
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn resume(&mut self) {
+        self.update();
+        self.running_since = Some(Instant::now());
+    }
+
+    pub fn pause(&mut self) {
+        self.update();
+        self.running_since = None;
+    }
+
+    /// Adds the time since the last update, up to MAX_UPDATE_GAP.
+    pub fn update(&mut self) {
+        let now = Instant::now();
+        if let Some(since) = self.running_since {
+            self.running_since = Some(now);
+            let gap = now - since;
+            if gap > MAX_UPDATE_GAP {
+                info!("Counting {:?} without updates as {:?}", gap, MAX_UPDATE_GAP);
+            }
+            self.elapsed += gap.min(MAX_UPDATE_GAP);
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+/// Minutes and seconds as shown on the timer. Rounds up, so
+/// 0:00 only shows once the time is up.
+pub fn format(remaining: Duration) -> String {
+    let secs = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    /// A running clock that was last updated `ago`.
+    fn updated(ago: Duration) -> Clock {
+        Clock {
+            elapsed: Duration::ZERO,
+            running_since: Some(Instant::now() - ago),
+        }
+    }
+
+    #[test]
+    fn formats_minutes_and_seconds() {
+        assert_eq!(format(Duration::from_secs(180)), "3:00");
+        assert_eq!(format(Duration::from_secs(65)), "1:05");
+        assert_eq!(format(Duration::from_millis(59_001)), "1:00");
+        assert_eq!(format(Duration::from_millis(1)), "0:01");
+        assert_eq!(format(Duration::ZERO), "0:00");
+    }
+
+    #[test]
+    fn counts_while_running() {
+        let mut clock = updated(Duration::from_secs(2));
+        clock.update();
+        assert!(clock.elapsed() >= Duration::from_secs(2));
+        assert!(clock.elapsed() < Duration::from_secs(3));
+    }
+
+    #[test]
+    fn waits_while_paused() {
+        let mut clock = Clock::new();
+        clock.update();
+        assert_eq!(clock.elapsed(), Duration::ZERO);
+        let mut clock = updated(Duration::from_secs(2));
+        clock.pause();
+        let elapsed = clock.elapsed();
+        thread::sleep(Duration::from_millis(10));
+        clock.update();
+        assert_eq!(clock.elapsed(), elapsed);
+        clock.resume();
+        thread::sleep(Duration::from_millis(10));
+        clock.update();
+        assert!(clock.elapsed() > elapsed);
+    }
+
+    #[test]
+    fn caps_long_gaps() {
+        let mut clock = updated(Duration::from_secs(3600));
+        clock.update();
+        assert_eq!(clock.elapsed(), MAX_UPDATE_GAP);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::canvas::{Canvas, Refresh};
+use crate::config::{Action, Config};
+use crate::game::{Direction, Game};
+use crate::game_screen::{self, GAME_OVER_DELAY};
+use crate::layout::Layout;
+use crate::menu::Menu;
+use crate::screen::{Context, Input, Screen, Transition};
+use anyhow::Result;
+use std::thread;
+use std::time::Instant;
+
+/// Smallest tile that wins a versus game, whatever the rules.
+/// Makes it 256 in classic games.
+const TARGET_MIN: u32 = 256;
+const PLAYERS: usize = 2;
+
+/// Two players on one device, each swiping on their own half of the
+/// screen. Both boards start from the same seed. Whoever reaches the
+/// target first wins, and whoever gets stuck first loses.
+pub struct VersusScreen {
+    games: [Game; PLAYERS],
+    target: u32,
+}
+
+impl VersusScreen {
+    /// Boards in the configured mode and size with a random seed.
+    /// Time limits don't apply.
+    pub fn new(config: &Config) -> Self {
+        let seed = rand::random();
+        info!("Starting versus game with seed {}", seed);
+        let game = || {
+            Game::new(
+                config.mode,
+                config.rules(),
+                config.board_size,
+                seed,
+                config.start_walls(),
+            )
+        };
+        Self {
+            games: [game(), game()],
+            target: config.rules().first_tile_from(TARGET_MIN),
+        }
+    }
+
+    /// Points the view at the board of the player.
+    fn show(&self, ctx: &mut Context, player: usize) {
+        ctx.view.layout = Layout::versus(
+            ctx.config.orientation,
+            ctx.config.handedness,
+            self.games[player].size,
+            player,
+        );
+    }
+
+    fn status(&self, player: usize) -> String {
+        format!(
+            "Player {}: {} of {}",
+            player + 1,
+            self.games[player].max_tile(),
+            self.target
+        )
+    }
+
+    /// Moves the tiles of the player and spawns a new one.
+    fn play(
+        &mut self,
+        ctx: &mut Context,
+        player: usize,
+        direction: Direction,
+    ) -> Result<Transition> {
+        self.show(ctx, player);
+        let (canvas, view, game) = (&mut ctx.canvas, &mut ctx.view, &mut self.games[player]);
+        let start = Instant::now();
+        let last = game.cells().to_vec();
+        if !game.play(direction) {
+            return Ok(Transition::Stay);
+        }
+        let markers = view.draw_cells(canvas, Some(&last), game.cells())?;
+        canvas.wait_for_updates(&markers);
+        thread::sleep(ctx.config.spawn_delay().saturating_sub(start.elapsed()));
+        let last = game.cells().to_vec();
+        if game.spawn_tile().is_some() {
+            let markers = view.animate_spawned_tiles(canvas, &last, game.cells())?;
+            canvas.wait_for_updates(&markers);
+        }
+        let last = game.cells().to_vec();
+        if game.make_room().is_some() {
+            thread::sleep(ctx.config.spawn_delay());
+            let markers = view.draw_cells(canvas, Some(&last), game.cells())?;
+            canvas.wait_for_updates(&markers);
+        }
+        debug!(
+            "Move {:?} of player {} took {:?}",
+            direction,
+            player + 1,
+            start.elapsed()
+        );
+
+        let game = &self.games[player];
+        let other = (player + 1) % PLAYERS;
+        if game.max_tile() >= self.target {
+            return Ok(self.over(ctx, player, self.status(other)));
+        }
+        if !game.can_move() {
+            return Ok(self.over(ctx, other, format!("Player {} is stuck", player + 1)));
+        }
+        ctx.view.draw_status(&mut ctx.canvas, &self.status(player));
+        Ok(Transition::Stay)
+    }
+
+    /// Shows who won and offers a rematch.
+    fn over(&self, ctx: &mut Context, winner: usize, loser_status: String) -> Transition {
+        let title = format!("Player {} wins!", winner + 1);
+        info!(
+            "{} Max tiles: {}",
+            title,
+            self.games
+                .iter()
+                .map(|game| game.max_tile().to_string())
+                .collect::<Vec<_>>()
+                .join(" vs. ")
+        );
+        self.show(ctx, winner);
+        ctx.view.draw_status(&mut ctx.canvas, &title);
+        self.show(ctx, (winner + 1) % PLAYERS);
+        ctx.view.draw_status(&mut ctx.canvas, &loser_status);
+        thread::sleep(GAME_OVER_DELAY);
+        Transition::Replace(Box::new(Menu::versus_over(title)))
+    }
+}
+
+impl Screen for VersusScreen {
+    /// Applies changes of the config that happened meanwhile.
+    fn on_enter(&mut self, ctx: &mut Context) -> Result<()> {
+        let game = &self.games[0];
+        ctx.view.reconfigure(&ctx.config, game.size, game.rules);
+        self.draw(ctx)?;
+        // Tiles are smaller than on a single board
+        ctx.view.prerender(&mut ctx.canvas);
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> Result<()> {
+        ctx.canvas.clear();
+        ctx.canvas.update_full(Refresh::Quality);
+        for player in 0..PLAYERS {
+            self.show(ctx, player);
+            ctx.view.draw_grid(&mut ctx.canvas);
+            ctx.view
+                .draw_cells(&mut ctx.canvas, None, self.games[player].cells())?;
+            ctx.view.draw_status(&mut ctx.canvas, &self.status(player));
+        }
+        ctx.view.draw_menu_button(&mut ctx.canvas);
+        Ok(())
+    }
+
+    fn handle_input(&mut self, ctx: &mut Context, input: Input) -> Result<Transition> {
+        Ok(match input {
+            // The first player swipes on the top or left half
+            Input::Swipe(swipe, player) if player < PLAYERS => {
+                let direction = game_screen::swipe_direction(ctx.canvas.orientation(), swipe);
+                return self.play(ctx, player, direction);
+            }
+            Input::Tap(pos) if Canvas::is_hitting(pos, ctx.view.layout.menu_button) => {
+                Transition::Push(Box::new(Menu::main(true)))
+            }
+            Input::Action(Action::Menu) => Transition::Push(Box::new(Menu::main(true))),
+            Input::Action(action) => game_screen::handle_action(ctx, action),
+            Input::Config(_) => {
+                ctx.canvas.set_orientation(ctx.config.orientation);
+                self.on_enter(ctx)?;
+                Transition::Stay
+            }
+            _ => Transition::Stay,
+        })
+    }
+
+    fn splits_swipes(&self) -> bool {
+        true
+    }
+}